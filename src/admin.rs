@@ -1,8 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "client")]
+use {
+    super::{client::ApiRequest, AlistClient, Error},
+    serde_json::json,
+    std::time::Duration,
+};
+
 // todo
 
 // user
 // meta
 // driver
 // storage
-// setting
 // task
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingItem {
+    pub key: String,
+    pub value: String,
+    pub help: String,
+    pub r#type: String,
+    pub options: String,
+    pub group: isize,
+    pub flag: isize,
+}
+
+/// 获取设置项 GET /api/admin/setting/get
+#[cfg(feature = "client")]
+pub async fn get_setting(server: &str, token: &str, key: &str) -> Result<SettingItem, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .get_setting(key)
+        .await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexParams {
+    // 需要建立索引的路径
+    pub paths: Vec<String>,
+    // 最大深度, -1 表示不限制
+    pub max_depth: i32,
+}
+
+impl Default for IndexParams {
+    fn default() -> Self {
+        IndexParams {
+            paths: vec!["/".to_string()],
+            max_depth: -1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexProgress {
+    // 已索引的对象数
+    pub obj_count: u64,
+    pub is_done: bool,
+    pub last_done_time: Option<String>,
+    pub error: String,
+}
+
+/// 重建索引 POST /api/admin/index/build
+#[cfg(feature = "client")]
+pub async fn build_index(server: &str, token: &str, params: IndexParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .build_index(params)
+        .await
+}
+
+/// 更新索引 POST /api/admin/index/update
+#[cfg(feature = "client")]
+pub async fn update_index(server: &str, token: &str, params: IndexParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .update_index(params)
+        .await
+}
+
+/// 停止索引 POST /api/admin/index/stop
+#[cfg(feature = "client")]
+pub async fn stop_index(server: &str, token: &str) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .stop_index()
+        .await
+}
+
+/// 清空索引 POST /api/admin/index/clear
+#[cfg(feature = "client")]
+pub async fn clear_index(server: &str, token: &str) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .clear_index()
+        .await
+}
+
+/// 获取索引进度 GET /api/admin/index/progress
+#[cfg(feature = "client")]
+pub async fn index_progress(server: &str, token: &str) -> Result<IndexProgress, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .index_progress()
+        .await
+}
+
+/// 重建索引并轮询进度直到完成, 每次获取到进度都会调用 on_progress
+#[cfg(feature = "client")]
+pub async fn rebuild_index<F>(
+    server: &str,
+    token: &str,
+    params: IndexParams,
+    interval: Duration,
+    on_progress: F,
+) -> Result<IndexProgress, Error>
+where
+    F: FnMut(&IndexProgress),
+{
+    AlistClient::new(server)
+        .with_token(token)
+        .rebuild_index(params, interval, on_progress)
+        .await
+}
+
+#[cfg(feature = "client")]
+impl AlistClient {
+    /// 获取设置项 GET /api/admin/setting/get
+    pub async fn get_setting(&self, key: &str) -> Result<SettingItem, Error> {
+        self.call(ApiRequest::get("/api/admin/setting/get").query("key", key))
+            .await
+    }
+
+    async fn post_index(&self, action: &str, body: serde_json::Value) -> Result<(), Error> {
+        let endpoint = format!("/api/admin/index/{}", action);
+        self.call_unit(ApiRequest::post(&endpoint, body)).await
+    }
+
+    /// 重建索引 POST /api/admin/index/build
+    pub async fn build_index(&self, params: IndexParams) -> Result<(), Error> {
+        self.post_index("build", json!(params)).await
+    }
+
+    /// 更新索引 POST /api/admin/index/update
+    pub async fn update_index(&self, params: IndexParams) -> Result<(), Error> {
+        self.post_index("update", json!(params)).await
+    }
+
+    /// 停止索引 POST /api/admin/index/stop
+    pub async fn stop_index(&self) -> Result<(), Error> {
+        self.post_index("stop", json!({})).await
+    }
+
+    /// 清空索引 POST /api/admin/index/clear
+    pub async fn clear_index(&self) -> Result<(), Error> {
+        self.post_index("clear", json!({})).await
+    }
+
+    /// 获取索引进度 GET /api/admin/index/progress
+    pub async fn index_progress(&self) -> Result<IndexProgress, Error> {
+        self.call(ApiRequest::get("/api/admin/index/progress"))
+            .await
+    }
+
+    /// 重建索引并轮询进度直到完成, 每次获取到进度都会调用 on_progress
+    pub async fn rebuild_index<F>(
+        &self,
+        params: IndexParams,
+        interval: Duration,
+        mut on_progress: F,
+    ) -> Result<IndexProgress, Error>
+    where
+        F: FnMut(&IndexProgress),
+    {
+        // 索引在服务端异步构建, 刚开始时进度可能仍是上一次的结果, 包括上一次的错误
+        let before = self.index_progress().await?;
+        self.build_index(params).await?;
+        loop {
+            self.sleep(interval).await?;
+            let progress = self.index_progress().await?;
+            on_progress(&progress);
+            if progress == before {
+                continue;
+            }
+            if !progress.error.is_empty() {
+                return Err(Error::Other(progress.error));
+            }
+            if progress.is_done && progress.last_done_time != before.last_done_time {
+                return Ok(progress);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use super::Error;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use {
+    super::{capability::Capability, client::ApiRequest, AlistClient},
+    serde_json::json,
+};
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ArchiveMetaParams {
+    // 压缩包路径
+    pub path: String,
+    // 目录密码
+    pub password: Option<String>,
+    // 是否强制刷新
+    pub refresh: Option<bool>,
+    // 压缩包密码
+    pub archive_pass: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub size: u128,
+    pub is_dir: bool,
+    pub modified: String,
+    pub sign: String,
+    pub thumb: String,
+    pub r#type: isize,
+    // 仅 meta 接口返回完整目录树
+    pub children: Option<Vec<ArchiveEntry>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveMeta {
+    pub comment: String,
+    // 是否加密
+    pub encrypted: bool,
+    // 部分格式不返回目录树, 需要使用 list 逐层获取
+    pub content: Option<Vec<ArchiveEntry>>,
+    pub raw_url: String,
+    pub sign: String,
+}
+
+/// 获取压缩包信息 POST /api/fs/archive/meta
+#[cfg(feature = "client")]
+pub async fn meta(
+    server: &str,
+    token: &str,
+    params: ArchiveMetaParams,
+) -> Result<ArchiveMeta, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .archive_meta(params)
+        .await
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ArchiveListParams {
+    // 压缩包路径
+    pub path: String,
+    // 目录密码
+    pub password: Option<String>,
+    // 是否强制刷新
+    pub refresh: Option<bool>,
+    // 压缩包密码
+    pub archive_pass: Option<String>,
+    // 压缩包内的目录
+    pub inner_path: String,
+    // 页数
+    pub page: Option<usize>,
+    // 每页数目
+    pub per_page: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveListData {
+    pub content: Vec<ArchiveEntry>,
+    pub total: usize,
+}
+
+/// 列出压缩包内的文件 POST /api/fs/archive/list
+#[cfg(feature = "client")]
+pub async fn list(
+    server: &str,
+    token: &str,
+    params: ArchiveListParams,
+) -> Result<ArchiveListData, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .archive_list(params)
+        .await
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct DecompressParams {
+    // 压缩包所在目录
+    pub src_dir: String,
+    // 压缩包名称
+    pub name: Vec<String>,
+    // 压缩包密码
+    pub archive_pass: Option<String>,
+    // 只解压压缩包内的该目录, 默认 /
+    pub inner_path: String,
+    // 解压前先完整缓存压缩包
+    pub cache_full: bool,
+    // 解压到以压缩包命名的新目录
+    pub put_into_new_dir: bool,
+    // 解压的目标目录
+    pub dst_dir: String,
+}
+
+/// 解压到目标目录, 由服务端创建解压任务 POST /api/fs/archive/decompress
+#[cfg(feature = "client")]
+pub async fn decompress(server: &str, token: &str, params: DecompressParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .archive_decompress(params)
+        .await
+}
+
+/// 压缩包内单个文件的下载地址 GET /ad/*path
+///
+/// sign 使用 meta 接口返回的签名
+pub fn inner_download_url(
+    server: &str,
+    path: &str,
+    inner_path: &str,
+    archive_pass: Option<&str>,
+    sign: &str,
+) -> Result<String, Error> {
+    let mut url = url::Url::parse(server).map_err(|e| Error::Other(e.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| Error::Other(format!("invalid server url: {}", server)))?
+        .pop_if_empty()
+        .push("ad")
+        .extend(path.split('/').filter(|s| !s.is_empty()));
+    url.query_pairs_mut()
+        .append_pair("inner", inner_path)
+        .append_pair("pass", archive_pass.unwrap_or_default())
+        .append_pair("sign", sign);
+    Ok(url.to_string())
+}
+
+/// 下载压缩包内的单个文件到本地
+#[cfg(feature = "client")]
+pub async fn download_inner(
+    server: &str,
+    token: &str,
+    params: ArchiveMetaParams,
+    inner_path: &str,
+    local_file: &str,
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .archive_download_inner(params, inner_path, local_file)
+        .await
+}
+
+#[cfg(feature = "client")]
+impl AlistClient {
+    /// 获取压缩包信息 POST /api/fs/archive/meta
+    pub async fn archive_meta(&self, params: ArchiveMetaParams) -> Result<ArchiveMeta, Error> {
+        self.require(Capability::Archive)?;
+        self.call(ApiRequest::post("/api/fs/archive/meta", json!(params)).idempotent())
+            .await
+    }
+
+    /// 列出压缩包内的文件 POST /api/fs/archive/list
+    pub async fn archive_list(&self, params: ArchiveListParams) -> Result<ArchiveListData, Error> {
+        self.require(Capability::Archive)?;
+        self.call(ApiRequest::post("/api/fs/archive/list", json!(params)).idempotent())
+            .await
+    }
+
+    /// 解压到目标目录, 由服务端创建解压任务 POST /api/fs/archive/decompress
+    pub async fn archive_decompress(&self, params: DecompressParams) -> Result<(), Error> {
+        self.require(Capability::Archive)?;
+        self.call_unit(ApiRequest::post(
+            "/api/fs/archive/decompress",
+            json!(params),
+        ))
+        .await
+    }
+
+    /// 下载压缩包内的单个文件到本地
+    pub async fn archive_download_inner(
+        &self,
+        params: ArchiveMetaParams,
+        inner_path: &str,
+        local_file: &str,
+    ) -> Result<(), Error> {
+        let archive_pass = params.archive_pass.clone();
+        let path = params.path.clone();
+        let meta = self.archive_meta(params).await?;
+        let url = inner_download_url(
+            self.server(),
+            &path,
+            inner_path,
+            archive_pass.as_deref(),
+            &meta.sign,
+        )?;
+        self.download_file("/ad", &url, true, local_file).await?;
+        Ok(())
+    }
+}
@@ -1,33 +1,23 @@
-use super::Response;
-use reqwest;
-use serde_json::json;
+#[cfg(feature = "client")]
+use {
+    super::{capability::Capability, client::ApiRequest, AlistClient, Error},
+    serde_json::json,
+};
 
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+#[cfg(feature = "client")]
 #[derive(Debug, Serialize, Deserialize)]
 struct AuthResponse {
     token: String,
 }
 
-pub async fn login(server: &str, username: &str, password: &str) -> Result<String, String> {
-    let url = format!("{}/api/auth/login/hash", server);
-    let resp: Response<AuthResponse> = reqwest::Client::new()
-        .post(url)
-        .json(&json!({
-            "username": username,
-            "password": sha256(password),
-        }))
-        .send()
+#[cfg(feature = "client")]
+pub async fn login(server: &str, username: &str, password: &str) -> Result<String, Error> {
+    AlistClient::new(server)
+        .login_token(username, password)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(resp.data.unwrap().token)
 }
 
 pub fn sha256(value: &str) -> String {
@@ -48,20 +38,45 @@ pub struct UserInfo {
     pub otp: bool,
 }
 
-pub async fn get_user_info(server: &str, token: &str) -> Result<UserInfo, String> {
-    let url = format!("{}/api/me", server);
-    let resp: Response<UserInfo> = reqwest::Client::new()
-        .get(url)
-        .header("Authorization", token)
-        .send()
-        .await
-        .unwrap()
-        .json()
+#[cfg(feature = "client")]
+pub async fn get_user_info(server: &str, token: &str) -> Result<UserInfo, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .get_user_info()
         .await
-        .unwrap();
+}
+
+#[cfg(feature = "client")]
+impl AlistClient {
+    /// 登录并返回 token, 不修改当前客户端 POST /api/auth/login/hash
+    ///
+    /// 不支持 login/hash 的旧版本服务端使用明文密码登录 POST /api/auth/login
+    pub async fn login_token(&self, username: &str, password: &str) -> Result<String, Error> {
+        let req = if self.capabilities().contains(Capability::LoginHash) {
+            ApiRequest::post(
+                "/api/auth/login/hash",
+                json!({
+                    "username": username,
+                    "password": sha256(password),
+                }),
+            )
+            .idempotent()
+        } else {
+            ApiRequest::post(
+                "/api/auth/login",
+                json!({
+                    "username": username,
+                    "password": password,
+                }),
+            )
+            .idempotent()
+        };
+        let resp: AuthResponse = self.call(req).await?;
+        Ok(resp.token)
+    }
 
-    if resp.code != 200 {
-        return Err(resp.message);
+    /// 获取当前用户信息 GET /api/me
+    pub async fn get_user_info(&self) -> Result<UserInfo, Error> {
+        self.call(ApiRequest::get("/api/me")).await
     }
-    Ok(resp.data.unwrap())
 }
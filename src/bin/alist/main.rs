@@ -0,0 +1,571 @@
+use alistapi::connection::{ConnectionConfig, ProxyConfig};
+use alistapi::profile::Config;
+use alistapi::timeout::Timeouts;
+use alistapi::vfs::split_path;
+use alistapi::{fs, AlistClient, CancellationToken, Error};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod shell;
+
+/// alist 命令行工具
+///
+/// 退出码: 0 成功, 1 本地错误, 2 参数错误, 3 网络错误, 4 请求参数错误(400),
+/// 5 未登录或无权限(401/403), 6 不存在(404), 7 服务端错误(500), 8 其它接口错误,
+/// 9 服务端版本不支持, 130 被 Ctrl-C 取消
+#[derive(Parser)]
+#[command(name = "alist", version)]
+struct Cli {
+    /// 使用配置文件中的连接, 指定后忽略 server/username/password/token 和代理、证书参数
+    #[arg(long, env = "ALIST_PROFILE")]
+    profile: Option<String>,
+    /// 服务端地址
+    #[arg(long, env = "ALIST_SERVER", default_value = "http://127.0.0.1:5244")]
+    server: String,
+    /// 用户名
+    #[arg(long, env = "ALIST_USERNAME")]
+    username: Option<String>,
+    /// 密码
+    #[arg(long, env = "ALIST_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// 已有的 token, 指定后不再登录
+    #[arg(long, env = "ALIST_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// 代理地址, 如 http://proxy.example.com:8080
+    #[arg(long, env = "ALIST_PROXY")]
+    proxy: Option<String>,
+    /// 额外信任的根证书(PEM)
+    #[arg(long, value_name = "FILE")]
+    cacert: Vec<std::path::PathBuf>,
+    /// 不校验服务端证书, 只应在测试环境中使用
+    #[arg(long)]
+    insecure: bool,
+    /// 以 json 格式输出
+    #[arg(long, global = true)]
+    json: bool,
+    /// 单次请求的超时(秒), 不包括上传和下载
+    #[arg(long, global = true, value_name = "SECS")]
+    timeout: Option<u64>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 列出目录
+    Ls {
+        #[arg(default_value = "/")]
+        path: String,
+        /// 目录密码
+        #[arg(long)]
+        dir_password: Option<String>,
+        #[arg(long)]
+        page: Option<usize>,
+        #[arg(long)]
+        per_page: Option<usize>,
+        /// 强制刷新
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// 查看文件/目录信息
+    Stat {
+        path: String,
+        /// 目录密码
+        #[arg(long)]
+        dir_password: Option<String>,
+    },
+    /// 新建文件夹
+    Mkdir { path: String },
+    /// 移动文件到目标目录
+    Mv {
+        #[arg(num_args = 2.., value_name = "SRC... DST_DIR")]
+        paths: Vec<String>,
+    },
+    /// 复制文件到目标目录
+    Cp {
+        #[arg(num_args = 2.., value_name = "SRC... DST_DIR")]
+        paths: Vec<String>,
+    },
+    /// 删除文件或文件夹
+    Rm {
+        #[arg(num_args = 1.., required = true)]
+        paths: Vec<String>,
+    },
+    /// 重命名
+    Rename { path: String, name: String },
+    /// 上传本地文件到远程目录
+    Put {
+        local: String,
+        remote_dir: String,
+        /// 远程文件名, 默认使用本地文件名
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// 下载远程文件
+    Get {
+        remote: String,
+        /// 本地路径, 默认使用远程文件名
+        local: Option<String>,
+        /// 目录密码
+        #[arg(long)]
+        dir_password: Option<String>,
+    },
+    /// 搜索文件或文件夹
+    Search {
+        keywords: String,
+        #[arg(long, default_value = "/")]
+        parent: String,
+        /// 0-全部 1-文件夹 2-文件
+        #[arg(long)]
+        scope: Option<u8>,
+        #[arg(long)]
+        page: Option<usize>,
+        #[arg(long)]
+        per_page: Option<usize>,
+    },
+    /// 检测服务端是否可用
+    Ping,
+    /// 查看站点设置
+    Settings,
+    /// 查看当前用户
+    Whoami,
+    /// 交互式 shell
+    Shell,
+    /// 启动本地 WebDAV 网关, 按 Ctrl-C 停止
+    #[cfg(feature = "webdav")]
+    Webdav {
+        /// 监听地址
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+    },
+    /// 启动本地 S3 兼容网关, 按 Ctrl-C 停止
+    #[cfg(feature = "s3")]
+    S3 {
+        /// 监听地址
+        #[arg(long, default_value = "127.0.0.1:9000")]
+        listen: std::net::SocketAddr,
+        /// bucket 对应的 alist 目录, 可以指定多次
+        #[arg(long = "bucket", value_name = "NAME=PATH", value_parser = parse_bucket, required = true)]
+        buckets: Vec<(String, String)>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            if cli.json {
+                let value = serde_json::json!({
+                    "code": err.code(),
+                    "message": err.to_string(),
+                });
+                println!("{}", value);
+            } else {
+                eprintln!("alist: {}", err);
+            }
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::Api { code, .. } => match code {
+            400 => 4,
+            401 | 403 => 5,
+            404 => 6,
+            500 => 7,
+            _ => 8,
+        },
+        Error::Http(_) | Error::Decode(_) | Error::Status(_) | Error::Timeout => 3,
+        Error::Cancelled => 130,
+        Error::Unsupported { .. } => 9,
+        Error::Io(_) | Error::Other(_) => 1,
+    }
+}
+
+/// 连接服务端, login 为 false 时只确定服务端地址
+async fn connect(cli: &Cli, login: bool) -> Result<AlistClient, Error> {
+    if let Some(name) = &cli.profile {
+        if !login {
+            let config = Config::load()?;
+            let (_, profile) = config.profile(Some(name))?;
+            return AlistClient::new(&profile.server).with_connection(profile.connection.clone());
+        }
+        return AlistClient::from_profile(Some(name)).await;
+    }
+    let mut client = AlistClient::new(&cli.server).with_connection(ConnectionConfig {
+        proxy: cli.proxy.as_deref().map(ProxyConfig::new),
+        root_certificates: cli.cacert.clone(),
+        identity: None,
+        accept_invalid_certs: cli.insecure,
+    })?;
+    if let Some(token) = &cli.token {
+        return Ok(client.with_token(token));
+    }
+    if let (true, Some(username), Some(password)) = (login, &cli.username, &cli.password) {
+        // 旧版本服务端的登录接口不同
+        client.detect_version().await?;
+        client.login(username, password).await?;
+    }
+    // 未提供账号时以游客身份访问
+    Ok(client)
+}
+
+/// 解析 --bucket NAME=/path
+#[cfg(feature = "s3")]
+fn parse_bucket(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.is_empty() && path.starts_with('/') => {
+            Ok((name.to_string(), path.to_string()))
+        }
+        _ => Err(format!("expected NAME=/path, got {}", value)),
+    }
+}
+
+/// 按父目录分组, 批量接口一次只能操作同一目录下的文件
+fn group_by_dir(paths: &[String]) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        let (dir, name) = split_path(path);
+        groups.entry(dir).or_default().push(name);
+    }
+    groups
+}
+
+fn print<T: Serialize>(json: bool, value: &T, human: impl FnOnce(&T)) -> Result<(), Error> {
+    if json {
+        let output =
+            serde_json::to_string_pretty(value).map_err(|e| Error::Other(e.to_string()))?;
+        println!("{}", output);
+    } else {
+        human(value);
+    }
+    Ok(())
+}
+
+/// 修改操作成功时没有输出, --json 时输出操作结果
+fn done(json: bool, value: serde_json::Value) -> Result<(), Error> {
+    print(json, &value, |_| {})
+}
+
+fn short_time(modified: &str) -> &str {
+    modified.get(..19).unwrap_or(modified)
+}
+
+async fn run(cli: &Cli) -> Result<(), Error> {
+    let login = !matches!(cli.command, Command::Ping | Command::Settings);
+    let mut client = connect(cli, login).await?;
+    if let Some(secs) = cli.timeout {
+        let timeouts = Timeouts {
+            total: Some(Duration::from_secs(secs)),
+            ..client.timeouts()
+        };
+        client = client.with_timeouts(timeouts);
+    }
+    // shell 自行处理 Ctrl-C, 其它命令收到 Ctrl-C 时取消请求并删除下载了一半的文件
+    if !matches!(cli.command, Command::Shell) {
+        let cancel = CancellationToken::new();
+        client = client.with_cancel(cancel.clone());
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        });
+    }
+    match &cli.command {
+        Command::Shell => {
+            let handle = tokio::runtime::Handle::current();
+            let client = client.clone();
+            tokio::task::spawn_blocking(move || shell::run(handle, client))
+                .await
+                .map_err(|e| Error::Other(e.to_string()))?
+        }
+        #[cfg(feature = "webdav")]
+        Command::Webdav { listen } => {
+            let server =
+                alistapi::webdav::WebDavServer::start(std::sync::Arc::new(client), *listen).await?;
+            eprintln!("WebDAV gateway listening on {}", server.url());
+            let _ = tokio::signal::ctrl_c().await;
+            Ok(())
+        }
+        #[cfg(feature = "s3")]
+        Command::S3 { listen, buckets } => {
+            let buckets = buckets.iter().cloned().collect();
+            let server = alistapi::s3::S3Server::start(client, buckets, *listen).await?;
+            eprintln!("S3 gateway listening on {}", server.url());
+            let _ = tokio::signal::ctrl_c().await;
+            Ok(())
+        }
+        Command::Ping => {
+            client.ping().await?;
+            print(cli.json, &"pong", |v| println!("{}", v))
+        }
+        Command::Settings => {
+            let settings = client.get_settings().await?;
+            print(cli.json, &settings, |settings| {
+                if let Ok(serde_json::Value::Object(map)) = serde_json::to_value(settings) {
+                    for (key, value) in map {
+                        println!("{} = {}", key, value.as_str().unwrap_or_default());
+                    }
+                }
+            })
+        }
+        Command::Whoami => {
+            let user = client.get_user_info().await?;
+            print(cli.json, &user, |user| {
+                println!("username:   {}", user.username);
+                println!("base_path:  {}", user.base_path);
+                println!("role:       {}", user.role);
+                println!("permission: {}", user.permission);
+            })
+        }
+        Command::Ls {
+            path,
+            dir_password,
+            page,
+            per_page,
+            refresh,
+        } => {
+            let params = fs::FileParams {
+                path: Some(path.clone()),
+                password: dir_password.clone(),
+                page: *page,
+                per_page: *per_page,
+                refresh: Some(*refresh),
+            };
+            let data = client.listdir(params).await?;
+            print(cli.json, &data, |data| {
+                for file in &data.content {
+                    println!(
+                        "{} {:>14} {} {}",
+                        if file.is_dir { "d" } else { "-" },
+                        file.size,
+                        short_time(&file.modified),
+                        file.name
+                    );
+                }
+            })
+        }
+        Command::Stat { path, dir_password } => {
+            let params = fs::FileParams {
+                path: Some(path.clone()),
+                password: dir_password.clone(),
+                ..Default::default()
+            };
+            let info = client.fileinfo(params).await?;
+            print(cli.json, &info, |info| {
+                println!("name:     {}", info.name);
+                println!("size:     {}", info.size);
+                println!("is_dir:   {}", info.is_dir);
+                println!("modified: {}", info.modified);
+                println!("provider: {}", info.provider);
+                println!("raw_url:  {}", info.row_url);
+            })
+        }
+        Command::Mkdir { path } => {
+            client.mkdir(path).await?;
+            done(cli.json, serde_json::json!({ "path": path }))
+        }
+        Command::Rename { path, name } => {
+            client.rename(path, name).await?;
+            done(cli.json, serde_json::json!({ "path": path, "name": name }))
+        }
+        Command::Mv { paths } | Command::Cp { paths } => {
+            let (dst_dir, srcs) = paths.split_last().expect("clap requires two paths");
+            for (src_dir, names) in group_by_dir(srcs) {
+                if matches!(cli.command, Command::Mv { .. }) {
+                    let params = fs::MoveParams {
+                        src_dir,
+                        dst_dir: dst_dir.clone(),
+                        names,
+                    };
+                    client.move_file(params).await?;
+                } else {
+                    let params = fs::CopyParams {
+                        src_dir,
+                        dst_dir: dst_dir.clone(),
+                        names,
+                    };
+                    client.copy_file(params).await?;
+                }
+            }
+            done(
+                cli.json,
+                serde_json::json!({ "paths": srcs, "dst_dir": dst_dir }),
+            )
+        }
+        Command::Rm { paths } => {
+            for (dir, names) in group_by_dir(paths) {
+                client
+                    .remove_directory(fs::DeleteParams { dir, names })
+                    .await?;
+            }
+            done(cli.json, serde_json::json!({ "paths": paths }))
+        }
+        Command::Put {
+            local,
+            remote_dir,
+            name,
+        } => {
+            let remote_name = match name {
+                Some(name) => name.clone(),
+                None => std::path::Path::new(local)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .ok_or_else(|| Error::Other(format!("invalid local file: {}", local)))?
+                    .to_string(),
+            };
+            let size = std::fs::metadata(local)?.len();
+            let remote_path = remote_dir.trim_end_matches('/').to_string();
+            let path = format!("{}/{}", remote_path, remote_name);
+            let params = fs::UploadParams {
+                local_file: local.clone(),
+                remote_path,
+                remote_name,
+            };
+            client.upload(params).await?;
+            done(cli.json, serde_json::json!({ "path": path, "size": size }))
+        }
+        Command::Get {
+            remote,
+            local,
+            dir_password,
+        } => {
+            let local = match local {
+                Some(local) => local.clone(),
+                None => split_path(remote).1,
+            };
+            let params = fs::FileParams {
+                path: Some(remote.clone()),
+                password: dir_password.clone(),
+                ..Default::default()
+            };
+            let size = client.download(params, &local).await?;
+            done(
+                cli.json,
+                serde_json::json!({ "path": remote, "local": local, "size": size }),
+            )
+        }
+        Command::Search {
+            keywords,
+            parent,
+            scope,
+            page,
+            per_page,
+        } => {
+            let params = fs::SearchParams {
+                parent: parent.clone(),
+                keywords: keywords.clone(),
+                scope: *scope,
+                page: *page,
+                per_page: *per_page,
+                password: None,
+            };
+            let data = client.search(params).await?;
+            print(cli.json, &data, |data| {
+                for file in &data.content {
+                    println!(
+                        "{} {}/{}",
+                        if file.is_dir { "d" } else { "-" },
+                        file.parent.trim_end_matches('/'),
+                        file.name
+                    );
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("alist").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        Cli::command().debug_assert();
+
+        let cli = parse(&["mv", "/cloud/a.txt", "/cloud/b.txt", "/backup", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(&cli.command, Command::Mv { paths } if paths.len() == 3));
+        let cli = parse(&["--timeout", "5", "get", "/cloud/a.txt"]).unwrap();
+        assert_eq!(cli.timeout, Some(5));
+        assert!(matches!(&cli.command, Command::Get { local: None, .. }));
+
+        // 参数错误的退出码为 2
+        for args in [
+            &["mv", "/cloud/a.txt"][..],
+            &["rm"],
+            &["--timeout", "soon", "ping"],
+            &["unknown"],
+        ] {
+            assert_eq!(parse(args).err().map(|err| err.exit_code()), Some(2));
+        }
+
+        let groups = group_by_dir(&[
+            "/cloud/a.txt".to_string(),
+            "/b.txt".to_string(),
+            "/cloud/sub/".to_string(),
+        ]);
+        assert_eq!(groups["/cloud"], ["a.txt", "sub"]);
+        assert_eq!(groups["/"], ["b.txt"]);
+    }
+
+    /// 执行命令并返回退出码
+    #[cfg(feature = "mock")]
+    async fn exit(cli: Cli) -> Result<(), u8> {
+        // run 的 future 较大, 放在堆上避免测试线程栈溢出
+        Box::pin(run(&cli)).await.map_err(|err| exit_code(&err))
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_exit_code() {
+        use alistapi::mock::MockServer;
+
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"hello");
+        let url = server.url();
+        let user = [
+            "--server",
+            &url,
+            "--username",
+            MockServer::USERNAME,
+            "--json",
+        ];
+        let login = |password: &str, args: &[&str]| {
+            let password = ["--password", password];
+            exit(parse(&[&user[..], &password, args].concat()).unwrap())
+        };
+        let password = MockServer::PASSWORD;
+
+        assert_eq!(login(password, &["mkdir", "/cloud/new"]).await, Ok(()));
+        assert!(server.exists("/cloud/new"));
+        let mv = ["mv", "/cloud/a.txt", "/cloud/new"];
+        assert_eq!(login(password, &mv).await, Ok(()));
+        assert!(server.exists("/cloud/new/a.txt"));
+        assert_eq!(login(password, &["stat", "/missing"]).await, Err(7));
+        assert_eq!(login("wrong", &["ls"]).await, Err(4));
+        let put = ["put", "/nonexistent/local.txt", "/cloud"];
+        assert_eq!(login(password, &put).await, Err(1));
+        // 游客没有权限
+        assert_eq!(
+            exit(parse(&["--server", &url, "ls"]).unwrap()).await,
+            Err(5)
+        );
+        assert_eq!(
+            exit(parse(&["--server", "http://127.0.0.1:1", "ping"]).unwrap()).await,
+            Err(3)
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use alistapi::vfs::{split_path, RemoteFs};
+use alistapi::{fs, AlistClient, Error};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::runtime::Handle;
+
+const COMMANDS: &[&str] = &[
+    "cat", "cd", "exit", "get", "help", "ls", "mkdir", "mv", "put", "pwd", "rm",
+];
+
+const HELP: &str = "\
+cd <dir>             切换目录
+ls [dir]             列出目录
+pwd                  显示当前目录
+cat <file>           输出文件内容
+get <file> [local]   下载文件
+put <local> [name]   上传文件到当前目录
+mkdir <dir>          新建文件夹
+mv <src...> <dir>    移动文件到目录
+rm <path...>         删除文件或文件夹
+exit                 退出";
+
+// (获取时间, 目录内容)
+type Cached<T> = (Instant, Vec<T>);
+
+// 补全使用的目录缓存的有效期, 其它客户端的修改在过期后才能看到
+const CACHE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct Shell {
+    handle: Handle,
+    client: AlistClient,
+    cwd: RefCell<String>,
+    // 目录 -> [(名称, 是否目录)], 修改操作后清空
+    files: RefCell<HashMap<String, Cached<(String, bool)>>>,
+    dirs: RefCell<HashMap<String, Cached<String>>>,
+}
+
+/// 交互式 shell, 需要在 spawn_blocking 中运行
+pub fn run(handle: Handle, client: AlistClient) -> Result<(), Error> {
+    let mut editor: Editor<Shell, DefaultHistory> =
+        Editor::new().map_err(|e| Error::Other(e.to_string()))?;
+    editor.set_helper(Some(Shell {
+        handle,
+        client,
+        cwd: RefCell::new("/".to_string()),
+        files: RefCell::new(HashMap::new()),
+        dirs: RefCell::new(HashMap::new()),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        let prompt = format!("alist:{}> ", editor.helper().unwrap().cwd.borrow());
+        match editor.readline(&prompt) {
+            Ok(line) => {
+                let args = split_args(&line);
+                if args.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(line.as_str());
+                if args[0] == "exit" || args[0] == "quit" {
+                    break;
+                }
+                if let Err(err) = editor.helper().unwrap().execute(&args) {
+                    eprintln!("{}: {}", args[0], err);
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(Error::Other(err.to_string())),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = editor.save_history(path);
+    }
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("alistapi").join("shell_history"))
+}
+
+/// 按空白拆分参数, 支持引号和反斜杠转义
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_arg = true;
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_whitespace() || matches!(c, '\\' | '"' | '\'') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 相对 cwd 解析路径, 处理 . 和 ..
+fn resolve(cwd: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd, path)
+    };
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+impl Shell {
+    fn path(&self, arg: &str) -> String {
+        resolve(&self.cwd.borrow(), arg)
+    }
+
+    fn invalidate(&self) {
+        self.files.borrow_mut().clear();
+        self.dirs.borrow_mut().clear();
+    }
+
+    /// 列出目录, cached 为 true 时使用未过期的缓存
+    fn list(&self, dir: &str, cached: bool) -> Result<Vec<(String, bool)>, Error> {
+        if let Some((time, files)) = self.files.borrow().get(dir) {
+            if cached && time.elapsed() < CACHE_TTL {
+                return Ok(files.clone());
+            }
+        }
+        let params = fs::FileParams {
+            path: Some(dir.to_string()),
+            ..Default::default()
+        };
+        let data = self.handle.block_on(self.client.listdir(params))?;
+        let files: Vec<(String, bool)> = data
+            .content
+            .into_iter()
+            .map(|f| (f.name, f.is_dir))
+            .collect();
+        self.files
+            .borrow_mut()
+            .insert(dir.to_string(), (Instant::now(), files.clone()));
+        Ok(files)
+    }
+
+    fn list_dirs(&self, dir: &str) -> Result<Vec<String>, Error> {
+        if let Some((time, dirs)) = self.dirs.borrow().get(dir) {
+            if time.elapsed() < CACHE_TTL {
+                return Ok(dirs.clone());
+            }
+        }
+        let params = fs::GetDirParams {
+            parent: dir.to_string(),
+            ..Default::default()
+        };
+        let data = self.handle.block_on(self.client.get_dirs(params))?;
+        let dirs: Vec<String> = data.content.into_iter().map(|d| d.name).collect();
+        self.dirs
+            .borrow_mut()
+            .insert(dir.to_string(), (Instant::now(), dirs.clone()));
+        Ok(dirs)
+    }
+
+    fn execute(&self, args: &[String]) -> Result<(), Error> {
+        let command = args[0].as_str();
+        let args = &args[1..];
+        let arg = |i: usize| {
+            args.get(i)
+                .map(String::as_str)
+                .ok_or_else(|| Error::Other("missing argument, see `help`".to_string()))
+        };
+        match command {
+            "help" => println!("{}", HELP),
+            "pwd" => println!("{}", self.cwd.borrow()),
+            "cd" => {
+                let path = self.path(args.first().map_or("/", String::as_str));
+                let params = fs::FileParams {
+                    path: Some(path.clone()),
+                    ..Default::default()
+                };
+                let info = self.handle.block_on(self.client.fileinfo(params))?;
+                if !info.is_dir {
+                    return Err(Error::Other(format!("{} is not a directory", path)));
+                }
+                *self.cwd.borrow_mut() = path;
+            }
+            "ls" => {
+                let path = self.path(args.first().map_or(".", String::as_str));
+                // ls 总是重新获取, 同时更新补全的缓存
+                for (name, is_dir) in self.list(&path, false)? {
+                    if is_dir {
+                        println!("{}/", name);
+                    } else {
+                        println!("{}", name);
+                    }
+                }
+            }
+            "cat" => {
+                let path = self.path(arg(0)?);
+                let mut stdout = std::io::stdout().lock();
+                // 边下载边输出, 与其它请求一样使用客户端的超时设置
+                let last = self.handle.block_on(async {
+                    let mut reader = self.client.open_read(&path).await?;
+                    let mut buf = vec![0; 64 * 1024];
+                    let mut last = None;
+                    loop {
+                        let n = reader.read(&mut buf).await?;
+                        if n == 0 {
+                            return Ok::<_, Error>(last);
+                        }
+                        stdout.write_all(&buf[..n])?;
+                        last = Some(buf[n - 1]);
+                    }
+                })?;
+                if last != Some(b'\n') {
+                    stdout.write_all(b"\n")?;
+                }
+            }
+            "get" => {
+                let path = self.path(arg(0)?);
+                let local = match args.get(1) {
+                    Some(local) => local.clone(),
+                    None => split_path(&path).1,
+                };
+                let params = fs::FileParams {
+                    path: Some(path),
+                    ..Default::default()
+                };
+                let size = self.handle.block_on(self.client.download(params, &local))?;
+                println!("{} bytes -> {}", size, local);
+            }
+            "put" => {
+                let local = arg(0)?;
+                let remote_name = match args.get(1) {
+                    Some(name) => name.clone(),
+                    None => std::path::Path::new(local)
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .ok_or_else(|| Error::Other(format!("invalid local file: {}", local)))?
+                        .to_string(),
+                };
+                let params = fs::UploadParams {
+                    local_file: local.to_string(),
+                    remote_path: self.cwd.borrow().trim_end_matches('/').to_string(),
+                    remote_name,
+                };
+                self.handle.block_on(self.client.upload(params))?;
+                self.invalidate();
+            }
+            "mkdir" => {
+                let path = self.path(arg(0)?);
+                self.handle.block_on(self.client.mkdir(&path))?;
+                self.invalidate();
+            }
+            "mv" => {
+                if args.len() < 2 {
+                    return Err(Error::Other("usage: mv <src...> <dir>".to_string()));
+                }
+                let (dst, srcs) = args.split_last().unwrap();
+                let dst_dir = self.path(dst);
+                for src in srcs {
+                    let (src_dir, name) = split_path(&self.path(src));
+                    let params = fs::MoveParams {
+                        src_dir,
+                        dst_dir: dst_dir.clone(),
+                        names: vec![name],
+                    };
+                    self.handle.block_on(self.client.move_file(params))?;
+                }
+                self.invalidate();
+            }
+            "rm" => {
+                arg(0)?;
+                for path in args {
+                    let (dir, name) = split_path(&self.path(path));
+                    let params = fs::DeleteParams {
+                        dir,
+                        names: vec![name],
+                    };
+                    self.handle.block_on(self.client.remove_directory(params))?;
+                }
+                self.invalidate();
+            }
+            _ => return Err(Error::Other("unknown command, see `help`".to_string())),
+        }
+        Ok(())
+    }
+}
+
+impl Completer for Shell {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        // 当前单词的起始位置, 跳过被转义的空白
+        let mut start = 0;
+        let mut escaped = false;
+        for (i, c) in line.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c.is_whitespace() {
+                start = i + c.len_utf8();
+            }
+        }
+        let word = split_args(&line[start..]).pop().unwrap_or_default();
+
+        if line[..start].trim().is_empty() {
+            let pairs = COMMANDS
+                .iter()
+                .filter(|c| c.starts_with(&word))
+                .map(|c| Pair {
+                    display: c.to_string(),
+                    replacement: format!("{} ", c),
+                })
+                .collect();
+            return Ok((start, pairs));
+        }
+
+        let (dir_part, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word.as_str()),
+        };
+        let dir = self.path(dir_part);
+        let only_dirs = line.trim_start().starts_with("cd ");
+        let entries = if only_dirs {
+            self.list_dirs(&dir)
+                .map(|dirs| dirs.into_iter().map(|d| (d, true)).collect())
+        } else {
+            self.list(&dir, true)
+        };
+        let pairs = entries
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, is_dir)| {
+                let suffix = if is_dir { "/" } else { "" };
+                Pair {
+                    display: format!("{}{}", name, suffix),
+                    replacement: format!("{}{}", escape(&format!("{}{}", dir_part, name)), suffix),
+                }
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("  ls   /cloud "), ["ls", "/cloud"]);
+        assert_eq!(
+            split_args(r#"mv "a b.txt" 'c d' e\ f.txt /dst"#),
+            ["mv", "a b.txt", "c d", "e f.txt", "/dst"]
+        );
+        assert_eq!(split_args(r#"rm "" x"#), ["rm", "", "x"]);
+        assert!(split_args("   ").is_empty());
+        // 补全结果转义后可以原样解析
+        assert_eq!(
+            split_args(&escape(r#"it's a "file"\"#)),
+            [r#"it's a "file"\"#]
+        );
+
+        assert_eq!(resolve("/cloud", "../docs/./a.txt"), "/docs/a.txt");
+        assert_eq!(resolve("/cloud", "/"), "/");
+        assert_eq!(resolve("/", ".."), "/");
+        assert_eq!(resolve("/cloud", "sub/"), "/cloud/sub");
+    }
+}
@@ -0,0 +1,232 @@
+//! 同步接口, 需要启用 blocking feature
+//!
+//! 每个客户端内部持有一个单线程的 tokio 运行时, 不能在异步上下文中调用,
+//! 其它设置(重试、超时、代理等)先在异步客户端上配置, 再通过 from_async 转换.
+//!
+//! ```no_run
+//! use alistapi::blocking::AlistClient;
+//! use alistapi::fs::FileParams;
+//!
+//! let mut client = AlistClient::new("http://127.0.0.1:5244")?;
+//! client.login("admin", "123456")?;
+//! client.mkdir("/cloud/reports")?;
+//! let data = client.listdir(FileParams {
+//!     path: Some("/cloud".to_string()),
+//!     ..Default::default()
+//! })?;
+//! # Ok::<(), alistapi::Error>(())
+//! ```
+use super::admin::{IndexParams, IndexProgress, SettingItem};
+use super::archive::{
+    ArchiveListData, ArchiveListParams, ArchiveMeta, ArchiveMetaParams, DecompressParams,
+};
+use super::auth::UserInfo;
+use super::capability::{Capabilities, ServerVersion};
+use super::chunker::ChunkManifest;
+use super::fs::*;
+use super::profile::Profile;
+use super::public::Settings;
+use super::Error;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// 同步的 alist 客户端, 方法与 [`crate::AlistClient`] 相同
+#[derive(Debug, Clone)]
+pub struct AlistClient {
+    inner: crate::AlistClient,
+    runtime: Arc<Runtime>,
+}
+
+// 生成直接转发到异步客户端的方法
+macro_rules! blocking {
+    ($($(#[$doc:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret, Error> {
+                self.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+impl AlistClient {
+    /// 未登录的客户端, 以游客身份访问, 无法创建运行时返回 Error::Io
+    pub fn new(server: &str) -> Result<Self, Error> {
+        AlistClient::from_async(crate::AlistClient::new(server))
+    }
+
+    /// 使用已配置好的异步客户端, 无法创建运行时返回 Error::Io
+    pub fn from_async(inner: crate::AlistClient) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(AlistClient {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.inner = self.inner.with_token(token);
+        self
+    }
+
+    pub fn as_async(&self) -> &crate::AlistClient {
+        &self.inner
+    }
+
+    pub fn into_async(self) -> crate::AlistClient {
+        self.inner
+    }
+
+    pub fn server(&self) -> &str {
+        self.inner.server()
+    }
+
+    pub fn token(&self) -> &str {
+        self.inner.token()
+    }
+
+    pub fn version(&self) -> Option<ServerVersion> {
+        self.inner.version()
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn block_on<F: Future>(&self, fut: F) -> F::Output {
+        self.runtime.block_on(fut)
+    }
+
+    /// 登录并保存 token
+    pub fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.runtime
+            .clone()
+            .block_on(self.inner.login(username, password))
+    }
+
+    /// 从站点设置获取并缓存服务端版本
+    pub fn detect_version(&mut self) -> Result<Option<ServerVersion>, Error> {
+        self.runtime.clone().block_on(self.inner.detect_version())
+    }
+
+    /// 使用配置文件中的连接, name 为 None 时使用默认配置
+    pub fn from_profile(name: Option<&str>) -> Result<Self, Error> {
+        let client = AlistClient::new("")?;
+        let inner = client.block_on(crate::AlistClient::from_profile(name))?;
+        Ok(AlistClient { inner, ..client })
+    }
+
+    /// 使用配置连接, 登录得到的 token 会缓存到本地
+    pub fn connect(name: &str, profile: &Profile) -> Result<Self, Error> {
+        let client = AlistClient::new("")?;
+        let inner = client.block_on(crate::AlistClient::connect(name, profile))?;
+        Ok(AlistClient { inner, ..client })
+    }
+
+    blocking! {
+        /// 新建文件夹 POST /api/fs/mkdir
+        fn mkdir(&self, path: &str) -> ();
+        /// 重命名文件 POST /api/fs/rename
+        fn rename(&self, path: &str, name: &str) -> ();
+        /// 流式上传文件 PUT /api/fs/put
+        fn upload(&self, params: UploadParams) -> ();
+        /// 列出文件目录 POST /api/fs/list
+        fn listdir(&self, params: FileParams) -> ListdirData;
+        /// 获取某个文件/目录信息 POST /api/fs/get
+        fn fileinfo(&self, params: FileParams) -> FileInfo;
+        /// 下载文件到本地, 使用 fs/get 返回的 raw_url
+        fn download(&self, params: FileParams, local_file: &str) -> u64;
+        /// 上传文件, 超过 chunk_size 时分块上传
+        fn upload_chunked(&self, params: UploadParams, chunk_size: u64) -> Option<ChunkManifest>;
+        /// 下载文件到本地, 分块保存的文件会合并为一个文件
+        fn download_chunked(&self, params: FileParams, local_file: &str) -> u64;
+        /// 获取视频转码播放信息 POST /api/fs/other
+        fn video_preview(&self, params: FileParams) -> VideoPreview;
+        /// 搜索文件或文件夹 POST /api/fs/search
+        fn search(&self, params: SearchParams) -> SearchFileData;
+        /// 获取目录 POST /api/fs/dirs
+        fn get_dirs(&self, params: GetDirParams) -> SearchDirData;
+        /// 批量重命名 POST /api/fs/batch_rename
+        fn batch_rename(&self, params: BatchRenameParams) -> ();
+        /// 正则重命名 POST /api/fs/regex_rename
+        fn regex_rename(&self, params: BatchRegexRenameParams) -> ();
+        /// 预览正则重命名结果, 不修改服务端文件
+        fn preview_regex_rename(&self, params: BatchRegexRenameParams) -> RegexRenamePlan;
+        /// 执行预览过的重命名计划, 存在冲突时拒绝执行
+        fn apply_regex_rename(&self, plan: &RegexRenamePlan) -> ();
+        /// 移动文件 POST /api/fs/move
+        fn move_file(&self, params: MoveParams) -> ();
+        /// 聚合移动 POST /api/fs/recursive_move
+        fn recursive_move(&self, params: RecursiveMoveParams) -> ();
+        /// 复制文件 POST /api/fs/copy
+        fn copy_file(&self, params: CopyParams) -> ();
+        /// 删除文件或文件夹 POST /api/fs/remove
+        fn remove_directory(&self, params: DeleteParams) -> ();
+        /// 删除空文件夹 POST /api/fs/remove_empty_directory
+        fn remove_empty_directory(&self, src_dir: String) -> ();
+        /// 添加离线下载任务, 旧版本服务端使用 add_aria2 / add_qbit
+        fn add_offline_download(&self, params: OfflineDownloadParams) -> ();
+        /// 添加aria2下载
+        fn add_aria2_task(&self, params: OfflineTaskParams) -> ();
+        /// 添加qBittorrent下载
+        fn add_qbit_task(&self, params: OfflineTaskParams) -> ();
+        /// 登录获取 token, 不修改当前客户端 POST /api/auth/login/hash
+        fn login_token(&self, username: &str, password: &str) -> String;
+        /// 获取当前用户信息 GET /api/me
+        fn get_user_info(&self) -> UserInfo;
+        /// 检查服务端是否可用 GET /ping
+        fn ping(&self) -> ();
+        /// 获取站点设置 GET /api/public/settings
+        fn get_settings(&self) -> Settings;
+        /// 获取已配置的离线下载工具 GET /api/public/offline_download_tools
+        fn offline_download_tools(&self) -> Vec<String>;
+        /// 获取设置项 GET /api/admin/setting/get
+        fn get_setting(&self, key: &str) -> SettingItem;
+        /// 重建索引 POST /api/admin/index/build
+        fn build_index(&self, params: IndexParams) -> ();
+        /// 更新索引 POST /api/admin/index/update
+        fn update_index(&self, params: IndexParams) -> ();
+        /// 停止索引 POST /api/admin/index/stop
+        fn stop_index(&self) -> ();
+        /// 清空索引 POST /api/admin/index/clear
+        fn clear_index(&self) -> ();
+        /// 获取索引进度 GET /api/admin/index/progress
+        fn index_progress(&self) -> IndexProgress;
+        /// 获取压缩包信息 POST /api/fs/archive/meta
+        fn archive_meta(&self, params: ArchiveMetaParams) -> ArchiveMeta;
+        /// 列出压缩包内的文件 POST /api/fs/archive/list
+        fn archive_list(&self, params: ArchiveListParams) -> ArchiveListData;
+        /// 解压到目标目录, 由服务端创建解压任务 POST /api/fs/archive/decompress
+        fn archive_decompress(&self, params: DecompressParams) -> ();
+        /// 下载压缩包内的单个文件到本地
+        fn archive_download_inner(&self, params: ArchiveMetaParams, inner_path: &str, local_file: &str) -> ();
+    }
+
+    /// 调用驱动的扩展方法 POST /api/fs/other
+    pub fn fs_other<Data: DeserializeOwned>(
+        &self,
+        params: FileParams,
+        method: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<Data, Error> {
+        self.block_on(self.inner.fs_other(params, method, data))
+    }
+
+    /// 重建索引并轮询进度直到完成, 每次获取到进度都会调用 on_progress
+    pub fn rebuild_index<F>(
+        &self,
+        params: IndexParams,
+        interval: Duration,
+        on_progress: F,
+    ) -> Result<IndexProgress, Error>
+    where
+        F: FnMut(&IndexProgress),
+    {
+        self.block_on(self.inner.rebuild_index(params, interval, on_progress))
+    }
+}
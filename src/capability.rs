@@ -0,0 +1,124 @@
+use super::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// 服务端版本, 取自站点设置中的 version, 如 v3.42.0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        ServerVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// 解析 v3.42.0 / 3.42.0 / v3.42.0-beta 等格式, 开发版(dev)等无法解析时返回错误
+    pub fn parse(version: &str) -> Result<Self, Error> {
+        let invalid = || Error::Other(format!("invalid server version: {}", version));
+        let core = version.trim().trim_start_matches('v');
+        let core = core.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = core.split('.').map(|p| p.parse::<u32>());
+        let major = parts.next().and_then(Result::ok).ok_or_else(invalid)?;
+        let minor = parts.next().and_then(Result::ok).ok_or_else(invalid)?;
+        let patch = match parts.next() {
+            Some(patch) => patch.map_err(|_| invalid())?,
+            None => 0,
+        };
+        Ok(ServerVersion::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// 与服务端版本相关的功能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    // 使用 sha256 后的密码登录 POST /api/auth/login/hash
+    LoginHash,
+    // 统一的离线下载接口 POST /api/fs/add_offline_download
+    OfflineDownload,
+    // 旧版离线下载接口 POST /api/fs/add_aria2 和 /api/fs/add_qbit
+    LegacyOfflineDownload,
+    // 压缩包预览和解压 /api/fs/archive/*
+    Archive,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::LoginHash,
+        Capability::OfflineDownload,
+        Capability::LegacyOfflineDownload,
+        Capability::Archive,
+    ];
+
+    /// 支持该功能的版本范围 [since, until)
+    fn versions(self) -> (ServerVersion, Option<ServerVersion>) {
+        match self {
+            Capability::LoginHash => (ServerVersion::new(3, 8, 0), None),
+            Capability::OfflineDownload => (ServerVersion::new(3, 30, 0), None),
+            Capability::LegacyOfflineDownload => (
+                ServerVersion::new(3, 0, 0),
+                Some(ServerVersion::new(3, 30, 0)),
+            ),
+            Capability::Archive => (ServerVersion::new(3, 42, 0), None),
+        }
+    }
+
+    pub fn supported_by(self, version: &ServerVersion) -> bool {
+        let (since, until) = self.versions();
+        *version >= since && until.is_none_or(|until| *version < until)
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::LoginHash => "login/hash",
+            Capability::OfflineDownload => "add_offline_download",
+            Capability::LegacyOfflineDownload => "add_aria2/add_qbit",
+            Capability::Archive => "archive",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 服务端支持的功能
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Capabilities(BTreeSet<Capability>);
+
+impl Capabilities {
+    /// 版本未知时视为支持全部功能, 由服务端决定是否可用
+    pub fn all() -> Self {
+        Capabilities(Capability::ALL.into_iter().collect())
+    }
+
+    pub fn for_version(version: &ServerVersion) -> Self {
+        Capabilities(
+            Capability::ALL
+                .into_iter()
+                .filter(|c| c.supported_by(version))
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, capability: Capability) -> bool {
+        self.0.contains(&capability)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        self.0.iter().copied()
+    }
+}
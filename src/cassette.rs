@@ -0,0 +1,218 @@
+//! 录制/回放 HTTP 请求, 用于离线的接口契约测试
+//!
+//! 录制时请求和响应中的密码、token 会被替换为 `<redacted>`.
+//! 文件内容的下载(raw_url 和 /ad/)不经过 cassette.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), alistapi::Error> {
+//! use alistapi::{cassette::Cassette, fs, AlistClient};
+//! use std::sync::Arc;
+//!
+//! // 对真实服务端录制
+//! let cassette = Arc::new(Cassette::record("tests/fixtures/v3.42.0.json"));
+//! let mut client = AlistClient::new("http://127.0.0.1:5244").with_cassette(cassette.clone());
+//! client.login("admin", "password").await?;
+//! client.listdir(fs::FileParams::default()).await?;
+//! cassette.save()?;
+//!
+//! // 在测试中回放
+//! let cassette = Arc::new(Cassette::replay("tests/fixtures/v3.42.0.json")?);
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_cassette(cassette);
+//! client.listdir(fs::FileParams::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+use super::client::ApiRequest;
+use super::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const REDACTED: &str = "<redacted>";
+
+// 需要脱敏的字段
+const SECRET_KEYS: [&str; 4] = ["password", "archive_pass", "token", "otp_code"];
+
+/// 一次请求及其响应
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Interaction {
+    pub method: String,
+    pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    pub status: u16,
+    // 响应不是 json 时为字符串, 如 /ping 返回的 pong
+    pub response: Value,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    interactions: Vec<Interaction>,
+    // 回放时已使用过的记录
+    used: Vec<bool>,
+}
+
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    state: Mutex<State>,
+}
+
+impl Cassette {
+    /// 录制请求, 调用 save 后写入 path
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Record,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// 从 path 读取录制的请求进行回放
+    ///
+    /// 每条记录只会被使用一次, 按顺序匹配第一条未使用的相同请求
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let content = std::fs::read(path.as_ref())?;
+        let file: CassetteFile = serde_json::from_slice(&content).map_err(Error::Decode)?;
+        let used = vec![false; file.interactions.len()];
+        Ok(Cassette {
+            path: path.as_ref().to_path_buf(),
+            mode: Mode::Replay,
+            state: Mutex::new(State {
+                interactions: file.interactions,
+                used,
+            }),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// 写入录制的请求
+    pub fn save(&self) -> Result<(), Error> {
+        let file = CassetteFile {
+            interactions: self.interactions(),
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_vec_pretty(&file).map_err(Error::Decode)?;
+        std::fs::write(&self.path, content)?;
+        Ok(())
+    }
+
+    pub(crate) fn record_request(&self, req: &ApiRequest, status: u16, body: &[u8]) {
+        if self.mode != Mode::Record {
+            return;
+        }
+        let mut interaction = Interaction::from_request(req);
+        interaction.status = status;
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut value) => {
+                redact(&mut value);
+                interaction.response = value;
+            }
+            Err(_) => {
+                interaction.response = Value::String(String::from_utf8_lossy(body).into_owned());
+                interaction.raw = true;
+            }
+        }
+        self.state.lock().unwrap().interactions.push(interaction);
+    }
+
+    pub(crate) fn replay_request(&self, req: &ApiRequest) -> Result<(u16, Vec<u8>), Error> {
+        let expected = Interaction::from_request(req);
+        let mut state = self.state.lock().unwrap();
+        let State { interactions, used } = &mut *state;
+        let index = interactions
+            .iter()
+            .enumerate()
+            .position(|(i, recorded)| !used[i] && expected.same_request(recorded))
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "no recorded interaction for {} {}",
+                    expected.method, expected.endpoint
+                ))
+            })?;
+        used[index] = true;
+        let recorded = &interactions[index];
+        let body = match &recorded.response {
+            Value::String(text) if recorded.raw => text.clone().into_bytes(),
+            value => serde_json::to_vec(value).map_err(Error::Decode)?,
+        };
+        Ok((recorded.status, body))
+    }
+}
+
+impl Interaction {
+    fn from_request(req: &ApiRequest) -> Self {
+        let mut request = req.json.clone();
+        if let Some(value) = &mut request {
+            redact(value);
+        }
+        Interaction {
+            method: req.method.to_string(),
+            endpoint: req.endpoint.clone(),
+            query: req.query.clone(),
+            request,
+            headers: req.headers.iter().cloned().collect(),
+            status: 0,
+            response: Value::Null,
+            raw: false,
+        }
+    }
+
+    fn same_request(&self, other: &Interaction) -> bool {
+        self.method == other.method
+            && self.endpoint == other.endpoint
+            && self.query == other.query
+            && self.request == other.request
+            && self.headers == other.headers
+    }
+}
+
+/// 将密码和 token 替换为 `<redacted>`, 包括 key 为 token 的设置项
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let is_token_setting = map.get("key").and_then(Value::as_str) == Some("token");
+            for (key, item) in map.iter_mut() {
+                let secret =
+                    SECRET_KEYS.contains(&key.as_str()) || (is_token_setting && key == "value");
+                match item {
+                    Value::String(s) if secret && !s.is_empty() => {
+                        *item = Value::String(REDACTED.to_string())
+                    }
+                    _ => redact(item),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
@@ -0,0 +1,621 @@
+//! 分块保存大文件, 用于限制单个文件大小的存储
+//!
+//! 超过 chunk_size 的文件保存为 `名称.chunked` 目录, 其中包含编号的分块 `00001.part`, `00002.part`
+//! 等和记录大小、sha256 及分块列表的 `manifest.json`. 通过 [`ChunkedFs`] 列出时显示为一个文件,
+//! 读取时按顺序拼接各分块并校验 sha256. 不超过 chunk_size 的文件仍保存为普通文件.
+//! 上传时分块先写入 `名称.chunked.uploading`, 全部完成后才替换原文件, 上传失败时保留原先的版本.
+//!
+//! ```no_run
+//! use alistapi::fs::UploadParams;
+//! use alistapi::AlistClient;
+//!
+//! # async fn run() -> Result<(), alistapi::Error> {
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! // 每块 4 GiB 以内
+//! let manifest = client
+//!     .upload_chunked(
+//!         UploadParams {
+//!             local_file: "master.mov".to_string(),
+//!             remote_path: "/cloud/videos".to_string(),
+//!             remote_name: "master.mov".to_string(),
+//!         },
+//!         4000 * 1024 * 1024,
+//!     )
+//!     .await?;
+//! println!("uploaded {} parts", manifest.map_or(1, |m| m.parts.len()));
+//! # Ok(())
+//! # }
+//! ```
+use super::client::{AlistClient, ApiRequest};
+use super::fs::{FileParams, UploadParams};
+use super::middleware::BoxFuture;
+use super::vfs::{split_path, Metadata, Reader, RemoteFs, Writer};
+use super::Error;
+use bytes::Bytes;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_util::io::StreamReader;
+
+/// 分块目录的后缀
+pub const CHUNKED_SUFFIX: &str = ".chunked";
+// 上传中的分块目录的后缀, 完成后改名为分块目录
+const UPLOADING_SUFFIX: &str = ".uploading";
+// 替换时原先版本的后缀, 替换完成后删除
+const BACKUP_SUFFIX: &str = ".backup";
+const MANIFEST: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+// 读写时的缓冲大小
+const BUFFER: usize = 64 * 1024;
+
+/// 分块文件的描述, 保存在分块目录的 manifest.json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub version: u32,
+    // 原文件大小
+    pub size: u64,
+    pub chunk_size: u64,
+    // 原文件的 sha256, 十六进制小写
+    pub sha256: String,
+    pub parts: Vec<ChunkPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkPart {
+    // 分块目录下的文件名
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn chunk_dir(path: &str) -> String {
+    format!("{}{}", path.trim_end_matches('/'), CHUNKED_SUFFIX)
+}
+
+fn join(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// 将大文件分块保存到另一个文件系统
+#[derive(Debug)]
+pub struct ChunkedFs<F> {
+    inner: Arc<F>,
+    chunk_size: u64,
+}
+
+impl<F> Clone for ChunkedFs<F> {
+    fn clone(&self) -> Self {
+        ChunkedFs {
+            inner: self.inner.clone(),
+            chunk_size: self.chunk_size,
+        }
+    }
+}
+
+impl<F: RemoteFs + 'static> ChunkedFs<F> {
+    /// chunk_size 为每块的最大字节数, 应小于存储对单个文件的限制
+    pub fn new(inner: F, chunk_size: u64) -> Self {
+        ChunkedFs {
+            inner: Arc::new(inner),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// 读取分块文件的描述, path 为原文件路径
+    pub async fn manifest(&self, path: &str) -> Result<ChunkManifest, Error> {
+        let mut reader = self
+            .inner
+            .open_read(&join(&chunk_dir(path), MANIFEST))
+            .await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        let manifest: ChunkManifest = serde_json::from_slice(&data).map_err(Error::Decode)?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(Error::Other(format!(
+                "unsupported chunk manifest version {}",
+                manifest.version
+            )));
+        }
+        Ok(manifest)
+    }
+
+    fn writer(&self, path: &str) -> ChunkWriter {
+        let (pipe, input) = tokio::io::duplex(BUFFER);
+        let task = write_chunks(self.inner.clone(), path.to_string(), self.chunk_size, input);
+        ChunkWriter {
+            pipe,
+            task: Some(Box::pin(task)),
+            manifest: None,
+        }
+    }
+
+    /// 实际保存的路径和信息, 分块文件返回分块目录
+    async fn resolve(&self, path: &str) -> Result<(String, Metadata), Error> {
+        match self.inner.metadata(path).await {
+            Ok(meta) => Ok((path.to_string(), meta)),
+            Err(err) => match self.inner.metadata(&chunk_dir(path)).await {
+                Ok(meta) if meta.is_dir => Ok((chunk_dir(path), meta)),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl<F: RemoteFs + 'static> RemoteFs for ChunkedFs<F> {
+    /// 分块目录显示为一个文件, 大小为原文件大小
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Vec<Metadata>, Error>> {
+        Box::pin(async move {
+            let mut entries = self.inner.read_dir(path).await?;
+            for entry in &mut entries {
+                let Some(name) = entry.name.strip_suffix(CHUNKED_SUFFIX) else {
+                    continue;
+                };
+                if !entry.is_dir {
+                    continue;
+                }
+                // manifest 无法读取时仍显示为目录
+                if let Ok(manifest) = self.manifest(&join(path, name)).await {
+                    entry.name = name.to_string();
+                    entry.is_dir = false;
+                    entry.size = manifest.size;
+                }
+            }
+            Ok(entries)
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Metadata, Error>> {
+        Box::pin(async move {
+            let (resolved, meta) = self.resolve(path).await?;
+            if resolved == path {
+                return Ok(meta);
+            }
+            let manifest = self.manifest(path).await?;
+            Ok(Metadata {
+                name: split_path(path).1,
+                is_dir: false,
+                size: manifest.size,
+                modified: meta.modified,
+            })
+        })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        self.inner.create_dir(path)
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (resolved, _) = self.resolve(from).await?;
+            if resolved == from {
+                self.inner.rename(from, to).await
+            } else {
+                self.inner.rename(&resolved, &chunk_dir(to)).await
+            }
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a str, to_dir: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (resolved, _) = self.resolve(from).await?;
+            self.inner.copy(&resolved, to_dir).await
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (resolved, _) = self.resolve(path).await?;
+            self.inner.remove(&resolved).await
+        })
+    }
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Reader, Error>> {
+        Box::pin(async move {
+            let err = match self.inner.open_read(path).await {
+                Ok(reader) => return Ok(reader),
+                Err(err) => err,
+            };
+            // 不是分块文件时返回读取原路径的错误
+            let Ok(manifest) = self.manifest(path).await else {
+                return Err(err);
+            };
+            Ok(read_chunks(self.inner.clone(), chunk_dir(path), manifest))
+        })
+    }
+
+    /// 写入超过 chunk_size 时改为分块保存, 需要调用 shutdown 完成上传
+    fn create_write<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Writer, Error>> {
+        Box::pin(async move { Ok(Box::pin(self.writer(path)) as Writer) })
+    }
+}
+
+/// 按顺序读取各分块, 每块读完后校验大小和 sha256, 全部读完后校验原文件的大小和 sha256
+fn read_chunks<F: RemoteFs + 'static>(fs: Arc<F>, dir: String, manifest: ChunkManifest) -> Reader {
+    struct State<F> {
+        fs: Arc<F>,
+        dir: String,
+        parts: std::vec::IntoIter<ChunkPart>,
+        current: Option<(Reader, Sha256, u64, ChunkPart)>,
+        // 已读取的全部内容
+        total: Sha256,
+        read: u64,
+        size: u64,
+        sha256: String,
+    }
+
+    let state = State {
+        fs,
+        dir,
+        parts: manifest.parts.into_iter(),
+        current: None,
+        total: Sha256::new(),
+        read: 0,
+        size: manifest.size,
+        sha256: manifest.sha256,
+    };
+    let stream = futures_util::stream::try_unfold(state, |mut state| async move {
+        loop {
+            if let Some((reader, hash, size, part)) = &mut state.current {
+                let mut buf = vec![0u8; BUFFER];
+                let n = reader.read(&mut buf).await?;
+                if n > 0 {
+                    hash.update(&buf[..n]);
+                    state.total.update(&buf[..n]);
+                    *size += n as u64;
+                    state.read += n as u64;
+                    buf.truncate(n);
+                    return Ok(Some((Bytes::from(buf), state)));
+                }
+                let sha256 = hex(&std::mem::take(hash).finalize());
+                if *size != part.size || sha256 != part.sha256 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("chunk {} is corrupted", part.name),
+                    ));
+                }
+                state.current = None;
+            }
+            let Some(part) = state.parts.next() else {
+                let sha256 = hex(&std::mem::take(&mut state.total).finalize());
+                if state.read != state.size || sha256 != state.sha256 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "chunked file does not match its manifest",
+                    ));
+                }
+                return Ok(None);
+            };
+            let reader = state.fs.open_read(&join(&state.dir, &part.name)).await?;
+            state.current = Some((reader, Sha256::new(), 0, part));
+        }
+    });
+    Box::pin(StreamReader::new(stream))
+}
+
+/// 从 input 读取全部内容并分块写入, 只有一块时保存为普通文件
+async fn write_chunks<F: RemoteFs>(
+    fs: Arc<F>,
+    path: String,
+    chunk_size: u64,
+    mut input: DuplexStream,
+) -> Result<Option<ChunkManifest>, Error> {
+    let staging = begin_chunks(&*fs, &path).await?;
+    let result = async {
+        let mut total = Sha256::new();
+        let mut parts = Vec::new();
+        let mut buf = vec![0u8; BUFFER];
+        loop {
+            // 先读取再创建分块, 避免在结尾多出一个空的分块
+            let first = (chunk_size as usize).min(BUFFER);
+            let mut n = input.read(&mut buf[..first]).await?;
+            if n == 0 && !parts.is_empty() {
+                break;
+            }
+            let name = part_name(parts.len());
+            let mut writer = fs.create_write(&join(&staging, &name)).await?;
+            let (mut hash, mut size) = (Sha256::new(), 0u64);
+            while n > 0 {
+                writer.write_all(&buf[..n]).await?;
+                hash.update(&buf[..n]);
+                total.update(&buf[..n]);
+                size += n as u64;
+                let remaining = (chunk_size - size).min(BUFFER as u64) as usize;
+                if remaining == 0 {
+                    break;
+                }
+                n = input.read(&mut buf[..remaining]).await?;
+            }
+            writer.shutdown().await?;
+            parts.push(ChunkPart {
+                name,
+                size,
+                sha256: hex(&hash.finalize()),
+            });
+            if size < chunk_size {
+                break;
+            }
+        }
+        Ok((total, parts))
+    }
+    .await;
+    let (total, parts) = match result {
+        Ok(uploaded) => uploaded,
+        Err(err) => {
+            let _ = fs.remove(&staging).await;
+            return Err(err);
+        }
+    };
+    commit_chunks(&*fs, &path, &staging, chunk_size, total, parts).await
+}
+
+/// 从 file 的当前位置读取 size 字节, 返回这一段的 sha256
+async fn hash_range(
+    file: &mut tokio::fs::File,
+    size: u64,
+    total: &mut Sha256,
+) -> Result<String, Error> {
+    let mut reader = file.take(size);
+    let (mut hash, mut read) = (Sha256::new(), 0u64);
+    let mut buf = vec![0u8; BUFFER];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hash.update(&buf[..n]);
+        total.update(&buf[..n]);
+        read += n as u64;
+    }
+    if read != size {
+        return Err(Error::Other("local file changed during upload".to_string()));
+    }
+    Ok(hex(&hash.finalize()))
+}
+
+fn part_name(index: usize) -> String {
+    format!("{:05}.part", index + 1)
+}
+
+/// 创建空的暂存目录, 分块先上传到这里, 上传失败时原文件不受影响
+async fn begin_chunks<F: RemoteFs>(fs: &F, path: &str) -> Result<String, Error> {
+    let staging = format!("{}{}", chunk_dir(path), UPLOADING_SUFFIX);
+    // 上次上传中断留下的暂存目录
+    if fs.metadata(&staging).await.is_ok() {
+        fs.remove(&staging).await?;
+    }
+    fs.create_dir(&staging).await?;
+    Ok(staging)
+}
+
+/// 写入 manifest 后用暂存目录替换原先的普通文件或分块目录, 只有一块时保存为普通文件
+///
+/// 原先的版本先改名为备份, 替换完成后才删除, 替换失败时恢复备份.
+/// 只在原先的版本完好时删除暂存目录, 任何时候服务端上都至少有一份完整的文件
+async fn commit_chunks<F: RemoteFs>(
+    fs: &F,
+    path: &str,
+    staging: &str,
+    chunk_size: u64,
+    total: Sha256,
+    parts: Vec<ChunkPart>,
+) -> Result<Option<ChunkManifest>, Error> {
+    let single = match parts.as_slice() {
+        [part] => Some(part.name.clone()),
+        _ => None,
+    };
+    let manifest = match single {
+        Some(_) => None,
+        None => Some(ChunkManifest {
+            version: MANIFEST_VERSION,
+            size: parts.iter().map(|part| part.size).sum(),
+            chunk_size,
+            sha256: hex(&total.finalize()),
+            parts,
+        }),
+    };
+    if let Some(manifest) = &manifest {
+        if let Err(err) = write_manifest(fs, staging, manifest).await {
+            let _ = fs.remove(staging).await;
+            return Err(err);
+        }
+    }
+
+    let dir = chunk_dir(path);
+    // (原路径, 备份路径)
+    let mut backups = Vec::new();
+    let mut result = Ok(());
+    for old in [path.to_string(), dir.clone()] {
+        if fs.metadata(&old).await.is_err() {
+            continue;
+        }
+        let backup = format!("{}{}", old, BACKUP_SUFFIX);
+        result = backup_file(fs, &old, &backup).await;
+        if result.is_err() {
+            break;
+        }
+        backups.push((old, backup));
+    }
+    if result.is_ok() {
+        result = match &single {
+            Some(name) => fs.rename(&join(staging, name), path).await,
+            None => fs.rename(staging, &dir).await,
+        };
+    }
+    if let Err(err) = result {
+        for (old, backup) in backups.iter().rev() {
+            if let Err(restore) = fs.rename(backup, old).await {
+                // 原先的版本只剩备份, 保留暂存目录
+                return Err(Error::Other(format!(
+                    "{}; failed to restore {} from {}: {}, the upload is kept in {}",
+                    err, old, backup, restore, staging
+                )));
+            }
+        }
+        let _ = fs.remove(staging).await;
+        return Err(err);
+    }
+
+    // 新的版本已经就位, 清理失败不影响上传结果, 同时清理之前恢复失败留下的备份
+    for old in [path, dir.as_str()] {
+        let backup = format!("{}{}", old, BACKUP_SUFFIX);
+        if fs.metadata(&backup).await.is_ok() {
+            let _ = fs.remove(&backup).await;
+        }
+    }
+    if single.is_some() {
+        let _ = fs.remove(staging).await;
+    }
+    Ok(manifest)
+}
+
+async fn write_manifest<F: RemoteFs>(
+    fs: &F,
+    staging: &str,
+    manifest: &ChunkManifest,
+) -> Result<(), Error> {
+    let data = serde_json::to_vec_pretty(manifest).map_err(|e| Error::Other(e.to_string()))?;
+    let mut writer = fs.create_write(&join(staging, MANIFEST)).await?;
+    writer.write_all(&data).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// 将 old 改名为 backup, 先删除上次替换中断留下的备份
+async fn backup_file<F: RemoteFs>(fs: &F, old: &str, backup: &str) -> Result<(), Error> {
+    if fs.metadata(backup).await.is_ok() {
+        fs.remove(backup).await?;
+    }
+    fs.rename(old, backup).await
+}
+
+/// 写入的内容通过 pipe 交给 write_chunks, 在写入时推进上传, 未调用 shutdown 时放弃上传
+struct ChunkWriter {
+    pipe: DuplexStream,
+    task: Option<BoxFuture<'static, Result<Option<ChunkManifest>, Error>>>,
+    // 上传完成后的分块描述
+    manifest: Option<ChunkManifest>,
+}
+
+impl ChunkWriter {
+    /// 上传提前结束说明出错了
+    fn poll_task(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        let Some(task) = &mut self.task else {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        };
+        if let Poll::Ready(result) = task.as_mut().poll(cx) {
+            self.task = None;
+            result?;
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        Ok(())
+    }
+}
+
+impl AsyncWrite for ChunkWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.poll_task(cx)?;
+        Pin::new(&mut this.pipe).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.poll_task(cx)?;
+        Pin::new(&mut this.pipe).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(Pin::new(&mut this.pipe).poll_shutdown(cx))?;
+        let Some(task) = &mut this.task else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = ready!(task.as_mut().poll(cx));
+        this.task = None;
+        this.manifest = result?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AlistClient {
+    /// 上传文件, 超过 chunk_size 时分块上传, 返回分块文件的描述, 未分块时返回 None
+    ///
+    /// 各分块直接从本地文件的对应位置上传, 不需要额外的磁盘空间
+    pub async fn upload_chunked(
+        &self,
+        params: UploadParams,
+        chunk_size: u64,
+    ) -> Result<Option<ChunkManifest>, Error> {
+        let chunk_size = chunk_size.max(1);
+        let path = join(&params.remote_path, &params.remote_name);
+        let mut file = tokio::fs::File::open(&params.local_file).await?;
+        let filesize = file.metadata().await?.len();
+        let staging = begin_chunks(self, &path).await?;
+        let result = async {
+            let mut total = Sha256::new();
+            let mut parts = Vec::new();
+            let mut offset = 0;
+            // 空文件也保存为一块
+            while offset < filesize || parts.is_empty() {
+                let size = chunk_size.min(filesize - offset);
+                let sha256 = hash_range(&mut file, size, &mut total).await?;
+                let name = part_name(parts.len());
+                let req = ApiRequest::new(Method::PUT, "/api/fs/put")
+                    .header("File-Path", &join(&staging, &name))
+                    .upload_range(&params.local_file, offset..offset + size);
+                self.call_unit(req).await?;
+                parts.push(ChunkPart { name, size, sha256 });
+                offset += size;
+            }
+            Ok::<_, Error>((total, parts))
+        }
+        .await;
+        let (total, parts) = match result {
+            Ok(uploaded) => uploaded,
+            Err(err) => {
+                let _ = RemoteFs::remove(self, &staging).await;
+                return Err(err);
+            }
+        };
+        commit_chunks(self, &path, &staging, chunk_size, total, parts).await
+    }
+
+    /// 下载文件到本地, 分块保存的文件会合并为一个文件
+    pub async fn download_chunked(
+        &self,
+        params: FileParams,
+        local_file: &str,
+    ) -> Result<u64, Error> {
+        let path = params.path.unwrap_or_default();
+        let fs = ChunkedFs::new(self.clone(), u64::MAX);
+        let mut reader = fs.open_read(&path).await?;
+        let mut file = tokio::fs::File::create(local_file).await?;
+        let result = async {
+            let written = tokio::io::copy(&mut reader, &mut file).await?;
+            file.flush().await?;
+            Ok(written)
+        }
+        .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(local_file).await;
+        }
+        result
+    }
+}
@@ -0,0 +1,677 @@
+use super::capability::{Capabilities, Capability, ServerVersion};
+use super::cassette::Cassette;
+use super::connection::{ConnectionConfig, ResolvedConnection};
+use super::metrics::{code_label, error_label, Metrics};
+use super::middleware::{ApiResponse, Chain, Middleware, Next, RawRequest};
+use super::profile::{token_expires_at, CachedToken, Config, Profile, TokenCache};
+use super::retry::RetryPolicy;
+use super::timeout::{with_timeout, Timeouts};
+use super::trace::RequestSpan;
+use super::{Error, Response};
+use bytes::Bytes;
+use reqwest::{Body, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::codec::{BytesCodec, FramedRead};
+use tokio_util::sync::CancellationToken;
+
+/// alist 客户端, 保存服务端地址和登录 token
+///
+/// 服务端版本只在 connect / from_profile 时获取, 通过 new 创建的客户端
+/// 需要调用 detect_version 或 with_version, 否则视为支持全部接口,
+/// 旧版本服务端上不支持的接口会返回服务端的错误而不是 Error::Unsupported
+#[derive(Debug, Clone)]
+pub struct AlistClient {
+    server: String,
+    token: String,
+    http: reqwest::Client,
+    // 录制或回放请求, 用于离线测试
+    cassette: Option<Arc<Cassette>>,
+    // 服务端版本, 未知时不限制可用的接口
+    version: Option<ServerVersion>,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+    // 取消后所有请求返回 Error::Cancelled
+    cancel: Option<CancellationToken>,
+    metrics: Option<Arc<Metrics>>,
+    middleware: Chain,
+}
+
+/// 发往 alist 接口的请求
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    // 如 /api/fs/list
+    pub endpoint: String,
+    pub query: Vec<(String, String)>,
+    pub json: Option<Value>,
+    pub headers: Vec<(String, String)>,
+    // 作为请求体上传的本地文件, 重试时重新打开
+    pub upload: Option<String>,
+    // 只上传本地文件的这一段, 为 None 时上传整个文件
+    pub upload_range: Option<Range<u64>>,
+    // 重复执行不会改变服务端状态, 可以自动重试
+    pub idempotent: bool,
+}
+
+impl ApiRequest {
+    pub fn new(method: Method, endpoint: &str) -> Self {
+        ApiRequest {
+            method: method.clone(),
+            endpoint: endpoint.to_string(),
+            query: Vec::new(),
+            json: None,
+            headers: Vec::new(),
+            upload: None,
+            upload_range: None,
+            idempotent: method == Method::GET,
+        }
+    }
+
+    pub fn get(endpoint: &str) -> Self {
+        ApiRequest::new(Method::GET, endpoint)
+    }
+
+    pub fn post(endpoint: &str, json: Value) -> Self {
+        let mut req = ApiRequest::new(Method::POST, endpoint);
+        req.json = Some(json);
+        req
+    }
+
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// 只读取数据的 POST 请求
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    pub fn upload(mut self, local_file: &str) -> Self {
+        self.upload = Some(local_file.to_string());
+        self
+    }
+
+    /// 上传本地文件的一段, 用于分块上传大文件
+    pub fn upload_range(mut self, local_file: &str, range: Range<u64>) -> Self {
+        self.upload = Some(local_file.to_string());
+        self.upload_range = Some(range);
+        self
+    }
+}
+
+// 200 且为 json 的下载响应不超过该大小时检查是否为错误信息
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// 文件下载的响应, 通过 AlistClient::read_chunk 读取内容
+pub(crate) struct RawResponse {
+    inner: reqwest::Response,
+    // 检查是否为错误信息时已读取的内容
+    prefetched: Option<Bytes>,
+}
+
+impl RawResponse {
+    fn new(inner: reqwest::Response) -> Self {
+        RawResponse {
+            inner,
+            prefetched: None,
+        }
+    }
+
+    pub(crate) fn status(&self) -> u16 {
+        self.inner.status().as_u16()
+    }
+
+    fn is_json(&self) -> bool {
+        let content_type = self
+            .inner
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        content_type.starts_with("application/json")
+            && self
+                .inner
+                .content_length()
+                .is_none_or(|len| len <= MAX_ERROR_BODY as u64)
+    }
+}
+
+impl AlistClient {
+    /// 未登录的客户端, 以游客身份访问
+    pub fn new(server: &str) -> Self {
+        let timeouts = Timeouts::default();
+        AlistClient {
+            server: server.trim_end_matches('/').to_string(),
+            token: String::new(),
+            http: build_http(&timeouts, &ResolvedConnection::default())
+                .expect("failed to build http client"),
+            cassette: None,
+            version: None,
+            retry: RetryPolicy::default(),
+            timeouts,
+            cancel: None,
+            metrics: None,
+            middleware: Chain::default(),
+        }
+    }
+
+    /// 使用已有的 token, 不会获取服务端版本
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = token.to_string();
+        self
+    }
+
+    /// 通过 cassette 录制或回放请求, 回放时不会访问服务端
+    pub fn with_cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// 请求失败时的重试策略, 默认只重试只读请求
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// 超时设置, read 和 total 在每次请求时生效, clone 后修改只影响新的客户端
+    ///
+    /// 不会重新创建连接池, connect 在创建连接池时生效, 需要在 with_connection 之前设置
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// 代理和 TLS 设置, 证书文件无法读取或格式错误时返回错误
+    ///
+    /// 代理密码来自命令时会阻塞当前线程直到命令结束
+    pub fn with_connection(self, connection: ConnectionConfig) -> Result<Self, Error> {
+        self.with_resolved_connection(connection.resolve()?)
+    }
+
+    fn with_resolved_connection(mut self, connection: ResolvedConnection) -> Result<Self, Error> {
+        self.http = build_http(&self.timeouts, &connection)?;
+        Ok(self)
+    }
+
+    /// 取消 token 后, 进行中的请求和上传下载会停止并返回 Error::Cancelled
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// 记录请求数、耗时和传输字节数, 多个客户端可以共用同一个 Metrics
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// 添加中间件, 先添加的中间件在外层
+    pub fn with_middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.0.push(middleware);
+        self
+    }
+
+    /// 指定服务端版本, 不再从服务端获取
+    pub fn with_version(mut self, version: ServerVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub fn version(&self) -> Option<ServerVersion> {
+        self.version
+    }
+
+    /// 从站点设置获取并缓存服务端版本, 开发版等无法解析的版本返回 None
+    pub async fn detect_version(&mut self) -> Result<Option<ServerVersion>, Error> {
+        let settings = self.get_settings().await?;
+        self.version = ServerVersion::parse(&settings.version).ok();
+        Ok(self.version)
+    }
+
+    /// 服务端支持的功能, 版本未知时视为全部支持
+    pub fn capabilities(&self) -> Capabilities {
+        self.version
+            .as_ref()
+            .map_or_else(Capabilities::all, Capabilities::for_version)
+    }
+
+    /// 服务端不支持时返回 Error::Unsupported
+    pub(crate) fn require(&self, capability: Capability) -> Result<(), Error> {
+        match self.version {
+            Some(version) if !capability.supported_by(&version) => Err(Error::Unsupported {
+                capability,
+                version,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// 在 token 被取消时返回 Error::Cancelled
+    pub(crate) async fn cancellable<T, F>(&self, fut: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        match &self.cancel {
+            Some(cancel) => tokio::select! {
+                _ = cancel.cancelled() => Err(Error::Cancelled),
+                result = fut => result,
+            },
+            None => fut.await,
+        }
+    }
+
+    /// 可取消的等待, 用于重试和轮询
+    pub(crate) async fn sleep(&self, duration: std::time::Duration) -> Result<(), Error> {
+        self.cancellable(async {
+            tokio::time::sleep(duration).await;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 发送请求, 返回 HTTP 状态码和响应内容, 按重试策略重试
+    pub(crate) async fn send(&self, req: ApiRequest) -> Result<(u16, Vec<u8>), Error> {
+        let span = RequestSpan::request(&req);
+        let start = Instant::now();
+        let result = span.instrument(self.send_with_retry(&req, &span)).await;
+        span.finish(&result);
+        if let Some(metrics) = &self.metrics {
+            metrics.request(&req.endpoint, &code_label(&result), start.elapsed());
+        }
+        result
+    }
+
+    async fn send_with_retry(
+        &self,
+        req: &ApiRequest,
+        span: &RequestSpan,
+    ) -> Result<(u16, Vec<u8>), Error> {
+        let mut attempt = 1;
+        loop {
+            span.attempt(attempt);
+            let result = Next::new(self, span, &self.middleware.0)
+                .run(req.clone())
+                .await
+                .map(|resp| (resp.status, resp.body));
+            let retryable = match &result {
+                Ok((status, body)) => self.retry.is_retryable_response(*status, body),
+                Err(e) => self.retry.should_retry_error(e, req.idempotent),
+            };
+            if !retryable || !self.retry.allows(attempt, req.idempotent) {
+                return result;
+            }
+            span.retry(attempt, result.as_ref().err());
+            if let Some(metrics) = &self.metrics {
+                metrics.retry(&req.endpoint);
+            }
+            self.sleep(self.retry.backoff(attempt)).await?;
+            attempt += 1;
+        }
+    }
+
+    /// 发送一次请求, 位于中间件链的最内层
+    pub(crate) async fn send_once(
+        &self,
+        req: &ApiRequest,
+        span: &RequestSpan,
+    ) -> Result<(u16, Vec<u8>), Error> {
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replay()) {
+            let (status, body) = cassette.replay_request(req)?;
+            span.response(status, &body);
+            return Ok((status, body));
+        }
+        // 上传耗时取决于文件大小, 不限制总时间
+        let total = match req.upload {
+            Some(_) => None,
+            None => self.timeouts.total,
+        };
+        let (status, body) = self
+            .cancellable(with_timeout(total, self.transfer(req, span)))
+            .await?;
+        span.response(status, &body);
+
+        if let Some(cassette) = &self.cassette {
+            cassette.record_request(req, status, &body);
+        }
+        Ok((status, body))
+    }
+
+    async fn transfer(
+        &self,
+        req: &ApiRequest,
+        span: &RequestSpan,
+    ) -> Result<(u16, Vec<u8>), Error> {
+        let url = format!("{}{}", self.server, req.endpoint);
+        let mut builder = self.http.request(req.method.clone(), url);
+        if !self.token.is_empty() {
+            builder = builder.header("Authorization", &self.token);
+        }
+        if !req.query.is_empty() {
+            builder = builder.query(&req.query);
+        }
+        for (key, value) in &req.headers {
+            builder = builder.header(key, value);
+        }
+        if let Some(json) = &req.json {
+            builder = builder.json(json);
+        }
+        let resp = match &req.upload {
+            Some(local_file) => {
+                let mut file = File::open(local_file).await?;
+                let filesize = match &req.upload_range {
+                    Some(range) => {
+                        file.seek(SeekFrom::Start(range.start)).await?;
+                        range.end.saturating_sub(range.start)
+                    }
+                    None => file.metadata().await?.len(),
+                };
+                span.sent(filesize);
+                let stream = FramedRead::new(file.take(filesize), BytesCodec::new());
+                builder = builder
+                    .header("Content-Length", filesize)
+                    .body(Body::wrap_stream(stream));
+                // 服务端在转存完成后才响应, 不限制等待时间
+                let resp = builder.send().await?;
+                if let Some(metrics) = &self.metrics {
+                    metrics.uploaded(filesize);
+                }
+                resp
+            }
+            None => self.send_raw(builder).await?,
+        };
+        let mut resp = RawResponse::new(resp);
+        let status = resp.status();
+        let mut body = Vec::new();
+        while let Some(chunk) = self.next_chunk(&mut resp).await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok((status, body))
+    }
+
+    /// 发送请求并等待响应头
+    async fn send_raw(&self, builder: RequestBuilder) -> Result<reqwest::Response, Error> {
+        self.cancellable(with_timeout(self.timeouts.read, async {
+            Ok(builder.send().await?)
+        }))
+        .await
+    }
+
+    /// 下载文件到本地, 出错或被取消时删除不完整的文件
+    ///
+    /// authorized 为 false 时不携带 token, 用于可能指向第三方存储的 raw_url,
+    /// endpoint 用于指标中的接口名称
+    pub(crate) async fn download_file(
+        &self,
+        endpoint: &str,
+        url: &str,
+        authorized: bool,
+        local_file: &str,
+    ) -> Result<u64, Error> {
+        let span = RequestSpan::download(url);
+        let start = Instant::now();
+        let result = span
+            .instrument(async {
+                let resp = self.get_raw(url, authorized, None, &span).await?;
+                let written = self.save_response(resp, local_file).await?;
+                span.received(written);
+                Ok(written)
+            })
+            .await;
+        span.finish(&result);
+        if let Some(metrics) = &self.metrics {
+            let code = match &result {
+                Ok(_) => "200".to_string(),
+                Err(err) => error_label(err),
+            };
+            metrics.request(endpoint, &code, start.elapsed());
+        }
+        result
+    }
+
+    /// 请求文件内容并等待响应头, 非 2xx 或返回 json 格式的错误信息时返回接口错误或 Error::Status
+    ///
+    /// 请求不经过中间件的 handle, 发送前调用每个中间件的 prepare_raw
+    ///
+    /// 指定 range 时只请求这部分内容, 服务端不支持时仍会返回 200 和完整内容
+    pub(crate) async fn get_raw(
+        &self,
+        url: &str,
+        authorized: bool,
+        range: Option<Range<u64>>,
+        span: &RequestSpan,
+    ) -> Result<RawResponse, Error> {
+        let mut req = RawRequest {
+            method: Method::GET,
+            url: url.to_string(),
+            headers: Vec::new(),
+            to_server: url.starts_with(&format!("{}/", self.server)),
+        };
+        if let Some(range) = range {
+            req.header("Range", &format!("bytes={}-{}", range.start, range.end - 1));
+        }
+        for middleware in &self.middleware.0 {
+            middleware.prepare_raw(&mut req)?;
+        }
+        let mut builder = self.http.request(req.method, req.url);
+        if authorized && !self.token.is_empty() {
+            builder = builder.header("Authorization", &self.token);
+        }
+        for (key, value) in &req.headers {
+            builder = builder.header(key, value);
+        }
+        let mut resp = RawResponse::new(self.send_raw(builder).await?);
+        let status = resp.status();
+        span.status(status);
+        if !(200..300).contains(&status) {
+            // alist 出错时返回 json 格式的错误信息
+            let mut body = Vec::new();
+            while let Some(chunk) = self.next_chunk(&mut resp).await? {
+                body.extend_from_slice(&chunk);
+            }
+            if let Ok(resp) = serde_json::from_slice::<Response<Value>>(&body) {
+                resp.check()?;
+            }
+            return Err(Error::Status(status));
+        }
+        // 签名错误或过期的 /d/ 和 /p/ 链接返回 200 和 json 格式的错误, 如 code 401 sign expired
+        if resp.is_json() {
+            let mut body = Vec::new();
+            let mut complete = false;
+            while body.len() <= MAX_ERROR_BODY {
+                match self.next_chunk(&mut resp).await? {
+                    Some(chunk) => body.extend_from_slice(&chunk),
+                    None => {
+                        complete = true;
+                        break;
+                    }
+                }
+            }
+            if complete {
+                if let Ok(resp) = serde_json::from_slice::<Response<Value>>(&body) {
+                    resp.check()?;
+                }
+            }
+            // 文件本身是 json, 已读取的内容仍作为文件内容返回
+            resp.prefetched = Some(Bytes::from(body));
+        }
+        Ok(resp)
+    }
+
+    /// 读取下一块响应数据, 超过 read 超时返回 Error::Timeout
+    ///
+    /// 所有文件下载都经过这里, 读取的字节数计入下载量
+    pub(crate) async fn read_chunk(&self, resp: &mut RawResponse) -> Result<Option<Bytes>, Error> {
+        let chunk = match resp.prefetched.take().filter(|c| !c.is_empty()) {
+            Some(chunk) => Some(chunk),
+            None => self.next_chunk(resp).await?,
+        };
+        if let (Some(metrics), Some(chunk)) = (&self.metrics, &chunk) {
+            metrics.downloaded(chunk.len() as u64);
+        }
+        Ok(chunk)
+    }
+
+    /// 从连接读取下一块数据, 不计入下载量, 用于接口响应和错误信息
+    async fn next_chunk(&self, resp: &mut RawResponse) -> Result<Option<Bytes>, Error> {
+        with_timeout(self.timeouts.read, async { Ok(resp.inner.chunk().await?) }).await
+    }
+
+    /// 将响应内容写入本地文件, 出错或被取消时删除不完整的文件
+    async fn save_response(&self, mut resp: RawResponse, local_file: &str) -> Result<u64, Error> {
+        let mut file = File::create(local_file).await?;
+        let result = self
+            .cancellable(async {
+                let mut written = 0;
+                while let Some(chunk) = self.read_chunk(&mut resp).await? {
+                    file.write_all(&chunk).await?;
+                    written += chunk.len() as u64;
+                }
+                file.flush().await?;
+                Ok(written)
+            })
+            .await;
+        if result.is_err() {
+            drop(file);
+            let _ = tokio::fs::remove_file(local_file).await;
+        }
+        result
+    }
+
+    /// 发送请求并解析为 Response, 非 2xx 且无法解析时返回 Error::Status
+    pub(crate) async fn execute<Data: DeserializeOwned>(
+        &self,
+        req: ApiRequest,
+    ) -> Result<Response<Data>, Error> {
+        let (status, body) = self.send(req).await?;
+        ApiResponse { status, body }.decode()
+    }
+
+    /// 发送请求并返回 data
+    pub(crate) async fn call<Data: DeserializeOwned>(
+        &self,
+        req: ApiRequest,
+    ) -> Result<Data, Error> {
+        self.execute(req).await?.into_data()
+    }
+
+    /// 发送请求, 只检查 code
+    pub(crate) async fn call_unit(&self, req: ApiRequest) -> Result<(), Error> {
+        self.execute::<Value>(req).await?.check()?;
+        Ok(())
+    }
+
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        self.token = self.login_token(username, password).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.token_refresh();
+        }
+        Ok(())
+    }
+
+    /// 按名称加载配置文件中的连接, 名称为空时使用默认配置
+    pub async fn from_profile(name: Option<&str>) -> Result<Self, Error> {
+        let config = Config::load()?;
+        let (name, profile) = config.profile(name)?;
+        AlistClient::connect(name, profile).await
+    }
+
+    /// 使用配置连接, 登录得到的 token 会缓存到本地, 过期前不再重复登录
+    ///
+    /// 连接时会获取服务端版本, 用于判断接口是否可用
+    pub async fn connect(name: &str, profile: &Profile) -> Result<Self, Error> {
+        let mut cache = TokenCache::load();
+        let (client, changed) = AlistClient::connect_with_cache(name, profile, &mut cache).await?;
+        if changed {
+            // 缓存写入失败不影响本次使用
+            let _ = cache.save();
+        }
+        Ok(client)
+    }
+
+    /// 使用给定的 token 缓存连接, 返回缓存是否有变化
+    pub(crate) async fn connect_with_cache(
+        name: &str,
+        profile: &Profile,
+        cache: &mut TokenCache,
+    ) -> Result<(Self, bool), Error> {
+        let mut client = AlistClient::new(&profile.server)
+            .with_resolved_connection(profile.connection.resolve_async().await?)?;
+        client.detect_version().await?;
+        if let Some(token) = &profile.token {
+            client.token = token.resolve_async().await?;
+            return Ok((client, false));
+        }
+        let (username, password) = match (&profile.username, &profile.password) {
+            (Some(username), Some(password)) => (username, password),
+            _ => return Ok((client, false)),
+        };
+
+        let mut changed = false;
+        if let Some(token) = cache.get(name, &client.server, username) {
+            client.token = token.to_string();
+            // 修改密码或服务端重启后 token 可能提前失效, 此时删除缓存重新登录
+            match client.get_user_info().await {
+                Err(err) if err.code() == Some(401) => {
+                    cache.remove(name);
+                    client.token.clear();
+                    changed = true;
+                }
+                result => return result.map(|_| (client, false)),
+            }
+        }
+        client
+            .login(username, &password.resolve_async().await?)
+            .await?;
+        if let Some(expires_at) = token_expires_at(&client.token) {
+            cache.insert(
+                name,
+                CachedToken {
+                    server: client.server.clone(),
+                    username: username.clone(),
+                    token: client.token.clone(),
+                    expires_at,
+                },
+            );
+            changed = true;
+        }
+        Ok((client, changed))
+    }
+}
+
+fn build_http(
+    timeouts: &Timeouts,
+    connection: &ResolvedConnection,
+) -> Result<reqwest::Client, Error> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(connect) = timeouts.connect {
+        builder = builder.connect_timeout(connect);
+    }
+    Ok(connection.apply(builder).build()?)
+}
@@ -0,0 +1,195 @@
+use super::profile::Secret;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+#[cfg(feature = "client")]
+use {super::Error, reqwest::ClientBuilder};
+
+/// 代理和 TLS 设置, 也可以写在配置文件中
+///
+/// TLS 默认使用 native-tls, 启用 rustls feature 后使用 rustls.
+///
+/// ```toml
+/// [profiles.office]
+/// server = "https://alist.corp.example.com"
+/// username = "admin"
+/// password = { env = "ALIST_PASSWORD" }
+///
+/// [profiles.office.connection]
+/// root_certificates = ["/etc/ssl/corp-ca.pem"]
+/// proxy = { url = "http://proxy.corp.example.com:8080", username = "me", password = { env = "PROXY_PASSWORD" } }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    pub proxy: Option<ProxyConfig>,
+    // 额外信任的根证书, PEM 格式, 一个文件中可以包含多个证书
+    pub root_certificates: Vec<PathBuf>,
+    // 客户端证书, 用于 mTLS
+    pub identity: Option<ClientIdentity>,
+    // 不校验服务端证书, 只应在测试环境中使用
+    pub accept_invalid_certs: bool,
+}
+
+/// HTTP/HTTPS 代理
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    // 如 http://proxy.example.com:8080
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    // 不使用代理的地址, 逗号分隔, 如 localhost,127.0.0.1,.lan
+    pub no_proxy: Option<String>,
+}
+
+/// PEM 格式的客户端证书和私钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientIdentity {
+    pub cert: PathBuf,
+    // PKCS#8 格式的私钥
+    pub key: PathBuf,
+}
+
+/// 已读取证书和代理密码的连接设置, 用于创建连接池
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResolvedConnection {
+    proxy: Option<reqwest::Proxy>,
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    root_certificates: Vec<reqwest::Certificate>,
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    identity: Option<reqwest::Identity>,
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    accept_invalid_certs: bool,
+}
+
+#[cfg(feature = "client")]
+impl ConnectionConfig {
+    /// 读取证书并获取代理密码, 密码来自命令时会阻塞当前线程
+    pub(crate) fn resolve(&self) -> Result<ResolvedConnection, Error> {
+        let password = match self.proxy.as_ref().and_then(|p| p.password.as_ref()) {
+            Some(password) => Some(password.resolve()?),
+            None => None,
+        };
+        self.resolve_with(password)
+    }
+
+    /// 读取证书并获取代理密码, 密码来自命令时不阻塞异步运行时
+    pub(crate) async fn resolve_async(&self) -> Result<ResolvedConnection, Error> {
+        let password = match self.proxy.as_ref().and_then(|p| p.password.as_ref()) {
+            Some(password) => Some(password.resolve_async().await?),
+            None => None,
+        };
+        self.resolve_with(password)
+    }
+
+    fn resolve_with(&self, proxy_password: Option<String>) -> Result<ResolvedConnection, Error> {
+        let mut resolved = ResolvedConnection::default();
+        if let Some(proxy) = &self.proxy {
+            resolved.proxy = Some(proxy.build(proxy_password)?);
+        }
+        if !self.root_certificates.is_empty()
+            || self.identity.is_some()
+            || self.accept_invalid_certs
+        {
+            self.resolve_tls(&mut resolved)?;
+        }
+        Ok(resolved)
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    fn resolve_tls(&self, resolved: &mut ResolvedConnection) -> Result<(), Error> {
+        for path in &self.root_certificates {
+            resolved
+                .root_certificates
+                .extend(reqwest::Certificate::from_pem_bundle(&read(path)?)?);
+        }
+        if let Some(identity) = &self.identity {
+            resolved.identity = Some(identity.load()?);
+        }
+        resolved.accept_invalid_certs = self.accept_invalid_certs;
+        Ok(())
+    }
+
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    fn resolve_tls(&self, _resolved: &mut ResolvedConnection) -> Result<(), Error> {
+        Err(Error::Other(
+            "TLS settings require the native-tls or rustls feature".to_string(),
+        ))
+    }
+}
+
+#[cfg(feature = "client")]
+impl ResolvedConnection {
+    /// 应用到 reqwest 客户端
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        #[cfg(any(feature = "native-tls", feature = "rustls"))]
+        {
+            for cert in &self.root_certificates {
+                builder = builder.add_root_certificate(cert.clone());
+            }
+            if let Some(identity) = &self.identity {
+                builder = builder.identity(identity.clone());
+            }
+            if self.accept_invalid_certs {
+                builder = builder.danger_accept_invalid_certs(true);
+            }
+        }
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        builder
+    }
+}
+
+impl ProxyConfig {
+    pub fn new(url: &str) -> Self {
+        ProxyConfig {
+            url: url.to_string(),
+            username: None,
+            password: None,
+            no_proxy: None,
+        }
+    }
+
+    #[cfg(feature = "client")]
+    fn build(&self, password: Option<String>) -> Result<reqwest::Proxy, Error> {
+        let mut proxy = reqwest::Proxy::all(&self.url)?;
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, &password.unwrap_or_default());
+        }
+        if let Some(no_proxy) = &self.no_proxy {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+        }
+        Ok(proxy)
+    }
+}
+
+#[cfg(feature = "client")]
+impl ClientIdentity {
+    #[cfg(feature = "rustls")]
+    fn load(&self) -> Result<reqwest::Identity, Error> {
+        // rustls 需要私钥和证书在同一个 PEM 中
+        let mut pem = read(&self.key)?;
+        pem.push(b'\n');
+        pem.extend(read(&self.cert)?);
+        Ok(reqwest::Identity::from_pem(&pem)?)
+    }
+
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    fn load(&self) -> Result<reqwest::Identity, Error> {
+        Ok(reqwest::Identity::from_pkcs8_pem(
+            &read(&self.cert)?,
+            &read(&self.key)?,
+        )?)
+    }
+}
+
+#[cfg(all(feature = "client", any(feature = "native-tls", feature = "rustls")))]
+fn read(path: &PathBuf) -> Result<Vec<u8>, Error> {
+    std::fs::read(path)
+        .map_err(|e| Error::Other(format!("failed to read {}: {}", path.display(), e)))
+}
@@ -0,0 +1,590 @@
+//! 客户端加密, 需要启用 crypt feature
+//!
+//! [`CryptFs`] 包装任意 [`RemoteFs`], 写入时加密文件内容和名称, 读取时透明解密, 密钥只保存在
+//! 本地. 文件内容按 64 KiB 分块, 使用 XChaCha20-Poly1305 加密, 每块的 nonce 包含块序号和是否为
+//! 最后一块, 可以检测内容被修改、重排或截断. 文件名使用由名称计算的 nonce 确定性加密, 同名文件
+//! 加密后的名称相同, 因此可以直接按路径访问.
+//!
+//! ```no_run
+//! use alistapi::crypt::{Cipher, CryptFs};
+//! use alistapi::vfs::RemoteFs;
+//! use alistapi::AlistClient;
+//! use tokio::io::AsyncWriteExt;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! let cipher = Cipher::new("password", "salt")?;
+//! let fs = CryptFs::new(client, "/cloud/private", cipher);
+//! fs.create_dir("/reports").await?;
+//! let mut writer = fs.create_write("/reports/2024.csv").await?;
+//! writer.write_all(b"id,amount\n").await?;
+//! writer.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+use super::middleware::BoxFuture;
+use super::vfs::{split_path, Metadata, Reader, RemoteFs, Writer};
+use super::Error;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// 加密文件开头的标识
+const MAGIC: &[u8; 8] = b"ALISTCR\x00";
+// 每个文件随机的 nonce 前缀, 后 5 字节为块序号和最后一块标记
+const NONCE_PREFIX: usize = 19;
+const HEADER: usize = MAGIC.len() + NONCE_PREFIX;
+const BLOCK: usize = 64 * 1024;
+const TAG: usize = 16;
+// 未指定 salt 时使用
+const DEFAULT_SALT: &[u8] = b"alistapi crypt";
+
+/// 文件名的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameEncryption {
+    // 不修改文件名
+    Off,
+    // 按名称轮换字母和数字, 只能避免直接看到文件名, 不能防止破解
+    Obfuscate,
+    // 加密后使用小写 base32 编码, 名称长度约增加为 1.6 倍加 52 个字符
+    #[default]
+    Encrypt,
+}
+
+/// 加密使用的密钥, 由密码派生, 不会发送到服务端
+#[derive(Clone)]
+pub struct Cipher {
+    data: XChaCha20Poly1305,
+    name: XChaCha20Poly1305,
+    name_mac: [u8; 32],
+    names: NameEncryption,
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cipher")
+            .field("names", &self.names)
+            .finish_non_exhaustive()
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Cipher {
+    /// 使用 scrypt 由密码派生密钥, salt 为空时使用默认值, 相同的密码和 salt 得到相同的密钥
+    pub fn new(password: &str, salt: &str) -> Result<Self, Error> {
+        let salt = match salt {
+            "" => DEFAULT_SALT,
+            salt => salt.as_bytes(),
+        };
+        let params = scrypt::Params::new(14, 8, 1, 32).map_err(|e| Error::Other(e.to_string()))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(Cipher::from_key(&key))
+    }
+
+    /// 直接使用 32 字节的主密钥
+    pub fn from_key(key: &[u8; 32]) -> Self {
+        let data = hmac(key, b"data");
+        let name = hmac(key, b"name");
+        Cipher {
+            data: XChaCha20Poly1305::new(&data.into()),
+            name: XChaCha20Poly1305::new(&name.into()),
+            name_mac: hmac(key, b"name-siv"),
+            names: NameEncryption::default(),
+        }
+    }
+
+    pub fn with_names(mut self, names: NameEncryption) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// 加密单个文件或目录名
+    pub fn encrypt_name(&self, name: &str) -> String {
+        if matches!(name, "" | "." | "..") {
+            return name.to_string();
+        }
+        match self.names {
+            NameEncryption::Off => name.to_string(),
+            NameEncryption::Obfuscate => {
+                let shift = hmac(&self.name_mac, name.as_bytes())[0];
+                format!("{}.{}", shift, rotate(name, shift as usize, false))
+            }
+            NameEncryption::Encrypt => {
+                // 由名称计算 nonce, 解密后再次计算用于校验
+                let siv = &hmac(&self.name_mac, name.as_bytes())[..16];
+                let mut nonce = XNonce::default();
+                nonce[..16].copy_from_slice(siv);
+                let sealed = self
+                    .name
+                    .encrypt(&nonce, name.as_bytes())
+                    .expect("encrypting in memory cannot fail");
+                base32_encode(&[siv, &sealed].concat())
+            }
+        }
+    }
+
+    /// 解密文件名, 不是由当前密钥加密的名称返回错误
+    pub fn decrypt_name(&self, name: &str) -> Result<String, Error> {
+        let invalid = || Error::Other(format!("failed to decrypt file name {}", name));
+        if matches!(name, "" | "." | "..") {
+            return Ok(name.to_string());
+        }
+        match self.names {
+            NameEncryption::Off => Ok(name.to_string()),
+            NameEncryption::Obfuscate => {
+                let (shift, rest) = name.split_once('.').ok_or_else(invalid)?;
+                let shift = shift.parse::<u8>().map_err(|_| invalid())?;
+                Ok(rotate(rest, shift as usize, true))
+            }
+            NameEncryption::Encrypt => {
+                let data = base32_decode(name).ok_or_else(invalid)?;
+                if data.len() < 16 + TAG {
+                    return Err(invalid());
+                }
+                let (siv, sealed) = data.split_at(16);
+                let mut nonce = XNonce::default();
+                nonce[..16].copy_from_slice(siv);
+                let plain = self.name.decrypt(&nonce, sealed).map_err(|_| invalid())?;
+                let mut mac =
+                    <HmacSha256 as Mac>::new_from_slice(&self.name_mac).expect("HMAC key");
+                mac.update(&plain);
+                mac.verify_truncated_left(siv).map_err(|_| invalid())?;
+                String::from_utf8(plain).map_err(|_| invalid())
+            }
+        }
+    }
+
+    /// 加密以 / 分隔的路径中的每一段
+    pub fn encrypt_path(&self, path: &str) -> String {
+        path.split('/')
+            .map(|segment| self.encrypt_name(segment))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// 加密后的文件大小
+    pub fn encrypted_size(size: u64) -> u64 {
+        let blocks = size.div_ceil(BLOCK as u64).max(1);
+        HEADER as u64 + size + blocks * TAG as u64
+    }
+
+    /// 解密后的文件大小, 不是有效的加密文件时返回 None
+    pub fn decrypted_size(size: u64) -> Option<u64> {
+        let body = size.checked_sub((HEADER + TAG) as u64)? + TAG as u64;
+        let (full, rest) = (body / (BLOCK + TAG) as u64, body % (BLOCK + TAG) as u64);
+        match rest {
+            0 => Some(full * BLOCK as u64),
+            rest if rest >= TAG as u64 => Some(full * BLOCK as u64 + rest - TAG as u64),
+            _ => None,
+        }
+    }
+
+    /// 包装 writer, 写入的内容加密后写入 writer, 需要调用 shutdown 写入最后一块
+    pub fn encrypt_writer<W: AsyncWrite + Unpin>(&self, writer: W) -> EncryptWriter<W> {
+        let mut prefix = [0u8; NONCE_PREFIX];
+        OsRng.fill_bytes(&mut prefix);
+        EncryptWriter {
+            inner: writer,
+            blocks: Blocks::new(self.data.clone(), prefix),
+            plain: Vec::with_capacity(BLOCK),
+            output: [MAGIC.as_slice(), &prefix].concat(),
+            written: 0,
+            finished: false,
+        }
+    }
+
+    /// 包装 reader, 读取时解密, 内容被修改或截断时返回 InvalidData 错误
+    pub fn decrypt_reader<R: AsyncRead + Unpin>(&self, reader: R) -> DecryptReader<R> {
+        DecryptReader {
+            inner: reader,
+            data: self.data.clone(),
+            blocks: None,
+            input: Vec::new(),
+            output: Vec::new(),
+            read: 0,
+            eof: false,
+            done: false,
+        }
+    }
+}
+
+/// 字母和数字按 shift 轮换
+fn rotate(name: &str, shift: usize, reverse: bool) -> String {
+    let turn = |c: char, base: u8, len: usize| {
+        let offset = (c as u8 - base) as usize;
+        let shift = shift % len;
+        let offset = match reverse {
+            false => (offset + shift) % len,
+            true => (offset + len - shift) % len,
+        };
+        (base + offset as u8) as char
+    };
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' => turn(c, b'a', 26),
+            'A'..='Z' => turn(c, b'A', 26),
+            '0'..='9' => turn(c, b'0', 10),
+            c => c,
+        })
+        .collect()
+}
+
+const BASE32: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// base32hex 小写编码, 不区分大小写的存储上也不会冲突
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let value = BASE32.iter().position(|&b| b == c.to_ascii_lowercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// 分块加解密, 每块的 nonce 为文件的随机前缀 + 4 字节块序号 + 最后一块标记
+struct Blocks {
+    aead: XChaCha20Poly1305,
+    prefix: [u8; NONCE_PREFIX],
+    counter: u32,
+}
+
+impl Blocks {
+    fn new(aead: XChaCha20Poly1305, prefix: [u8; NONCE_PREFIX]) -> Self {
+        Blocks {
+            aead,
+            prefix,
+            counter: 0,
+        }
+    }
+
+    fn nonce(&mut self, last: bool) -> io::Result<XNonce> {
+        let mut nonce = XNonce::default();
+        nonce[..NONCE_PREFIX].copy_from_slice(&self.prefix);
+        nonce[NONCE_PREFIX..NONCE_PREFIX + 4].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[NONCE_PREFIX + 4] = last as u8;
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| invalid_data("file is too large to encrypt"))?;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, plain: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.nonce(last)?;
+        self.aead
+            .encrypt(&nonce, plain)
+            .map_err(|_| invalid_data("failed to encrypt block"))
+    }
+
+    fn open(&mut self, sealed: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.nonce(last)?;
+        self.aead
+            .decrypt(&nonce, sealed)
+            .map_err(|_| invalid_data("encrypted file is corrupted or the key is wrong"))
+    }
+}
+
+/// 见 [`Cipher::encrypt_writer`]
+pub struct EncryptWriter<W> {
+    inner: W,
+    blocks: Blocks,
+    // 未加密的当前块, 写满后要等到有后续内容时才能确定不是最后一块
+    plain: Vec<u8>,
+    // 待写入 inner 的密文
+    output: Vec<u8>,
+    written: usize,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> EncryptWriter<W> {
+    fn poll_output(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.output.len() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.output[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.output.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for EncryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        ready!(this.poll_output(cx))?;
+        if this.plain.len() == BLOCK {
+            this.output = this.blocks.seal(&this.plain, false)?;
+            this.plain.clear();
+            ready!(this.poll_output(cx))?;
+        }
+        let n = buf.len().min(BLOCK - this.plain.len());
+        this.plain.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_output(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_output(cx))?;
+        if !this.finished {
+            this.output = this.blocks.seal(&this.plain, true)?;
+            this.plain.clear();
+            this.finished = true;
+            ready!(this.poll_output(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// 见 [`Cipher::decrypt_reader`]
+pub struct DecryptReader<R> {
+    inner: R,
+    data: XChaCha20Poly1305,
+    // 读取文件头后创建
+    blocks: Option<Blocks>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    read: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> DecryptReader<R> {
+    /// 读取直到 input 中有 len 字节或到达结尾
+    fn poll_fill(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<io::Result<()>> {
+        while !self.eof && self.input.len() < len {
+            let filled = self.input.len();
+            self.input.resize(len, 0);
+            let mut buf = ReadBuf::new(&mut self.input[filled..]);
+            let result = Pin::new(&mut self.inner).poll_read(cx, &mut buf);
+            let n = buf.filled().len();
+            self.input.truncate(filled + n);
+            ready!(result)?;
+            self.eof = n == 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DecryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.read < this.output.len() {
+                let n = buf.remaining().min(this.output.len() - this.read);
+                buf.put_slice(&this.output[this.read..this.read + n]);
+                this.read += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.done {
+                return Poll::Ready(Ok(()));
+            }
+            if this.blocks.is_none() {
+                ready!(this.poll_fill(cx, HEADER))?;
+                if this.input.len() < HEADER || !this.input.starts_with(MAGIC) {
+                    return Poll::Ready(Err(invalid_data("not an encrypted file")));
+                }
+                let mut prefix = [0u8; NONCE_PREFIX];
+                prefix.copy_from_slice(&this.input[MAGIC.len()..HEADER]);
+                this.input.drain(..HEADER);
+                this.blocks = Some(Blocks::new(this.data.clone(), prefix));
+            }
+            // 多读取 1 字节用于判断是否为最后一块
+            ready!(this.poll_fill(cx, BLOCK + TAG + 1))?;
+            let blocks = this.blocks.as_mut().expect("header has been read");
+            let last = this.input.len() <= BLOCK + TAG;
+            let len = this.input.len().min(BLOCK + TAG);
+            this.output = blocks.open(&this.input[..len], last)?;
+            this.input.drain(..len);
+            this.read = 0;
+            this.done = last;
+        }
+    }
+}
+
+/// 加密存储在另一个文件系统的 root 目录下, 路径为相对 root 的明文路径
+#[derive(Debug, Clone)]
+pub struct CryptFs<F> {
+    inner: F,
+    root: String,
+    cipher: Arc<Cipher>,
+}
+
+impl<F: RemoteFs> CryptFs<F> {
+    pub fn new(inner: F, root: &str, cipher: Cipher) -> Self {
+        CryptFs {
+            inner,
+            root: root.trim_end_matches('/').to_string(),
+            cipher: Arc::new(cipher),
+        }
+    }
+
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    pub fn cipher(&self) -> &Cipher {
+        &self.cipher
+    }
+
+    /// 明文路径对应的加密路径
+    ///
+    /// 加密名称时 . 和 .. 原样保留, 服务端整理路径后可能访问到 root 之外, 因此不允许
+    pub fn resolve(&self, path: &str) -> Result<String, Error> {
+        if path
+            .split('/')
+            .any(|segment| segment == "." || segment == "..")
+        {
+            return Err(Error::Other(format!(
+                "path must not contain . or .. segments: {}",
+                path
+            )));
+        }
+        Ok(match path.trim_matches('/') {
+            "" if self.root.is_empty() => "/".to_string(),
+            "" => self.root.clone(),
+            path => format!("{}/{}", self.root, self.cipher.encrypt_path(path)),
+        })
+    }
+
+    fn decrypt_metadata(&self, meta: Metadata) -> Result<Metadata, Error> {
+        let size = match meta.is_dir {
+            true => meta.size,
+            false => Cipher::decrypted_size(meta.size)
+                .ok_or_else(|| Error::Other(format!("{} is not an encrypted file", meta.name)))?,
+        };
+        Ok(Metadata {
+            name: self.cipher.decrypt_name(&meta.name)?,
+            size,
+            ..meta
+        })
+    }
+}
+
+impl<F: RemoteFs> RemoteFs for CryptFs<F> {
+    /// 不是由当前密钥加密的文件会被忽略
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Vec<Metadata>, Error>> {
+        Box::pin(async move {
+            let entries = self.inner.read_dir(&self.resolve(path)?).await?;
+            Ok(entries
+                .into_iter()
+                .filter_map(|meta| self.decrypt_metadata(meta).ok())
+                .collect())
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Metadata, Error>> {
+        Box::pin(async move {
+            let meta = self.inner.metadata(&self.resolve(path)?).await?;
+            // root 本身的名称没有加密
+            if path.trim_matches('/').is_empty() {
+                return Ok(meta);
+            }
+            self.decrypt_metadata(meta)
+        })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { self.inner.create_dir(&self.resolve(path)?).await })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (from, to) = (self.resolve(from)?, self.resolve(to)?);
+            self.inner.rename(&from, &to).await
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a str, to_dir: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (from, to_dir) = (self.resolve(from)?, self.resolve(to_dir)?);
+            self.inner.copy(&from, &to_dir).await
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            if split_path(path).1.is_empty() {
+                return Err(Error::Other("cannot remove the root directory".to_string()));
+            }
+            self.inner.remove(&self.resolve(path)?).await
+        })
+    }
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Reader, Error>> {
+        Box::pin(async move {
+            let reader = self.inner.open_read(&self.resolve(path)?).await?;
+            Ok(Box::pin(self.cipher.decrypt_reader(reader)) as Reader)
+        })
+    }
+
+    fn create_write<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Writer, Error>> {
+        Box::pin(async move {
+            let writer = self.inner.create_write(&self.resolve(path)?).await?;
+            Ok(Box::pin(self.cipher.encrypt_writer(writer)) as Writer)
+        })
+    }
+}
@@ -0,0 +1,102 @@
+use super::capability::{Capability, ServerVersion};
+use std::fmt;
+
+/// 接口函数返回的错误
+///
+/// 原先接口函数返回 Result<_, String>, 改为 Error 后调用方需要相应修改,
+/// 服务端错误的 to_string 与原先的 String 相同
+#[derive(Debug)]
+pub enum Error {
+    // 服务端返回的错误, code 即接口返回的 code
+    Api {
+        code: isize,
+        message: String,
+    },
+    // 网络错误
+    #[cfg(feature = "client")]
+    Http(reqwest::Error),
+    // 响应不是预期的 json 结构
+    Decode(serde_json::Error),
+    // 非 2xx 且响应无法解析, 如反向代理返回的错误页
+    Status(u16),
+    // 服务端版本不支持该功能
+    Unsupported {
+        capability: Capability,
+        version: ServerVersion,
+    },
+    // 超过 Timeouts 中设置的时间
+    Timeout,
+    // 通过 CancellationToken 取消
+    Cancelled,
+    // 本地文件读写错误
+    Io(std::io::Error),
+    // 其它错误, 如参数不合法
+    Other(String),
+}
+
+impl Error {
+    /// 接口返回的 code, 非服务端错误时为 None
+    pub fn code(&self) -> Option<isize> {
+        match self {
+            Error::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Api { message, .. } => write!(f, "{}", message),
+            #[cfg(feature = "client")]
+            Error::Http(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "invalid response: {}", e),
+            Error::Status(status) => write!(f, "HTTP status {}", status),
+            Error::Unsupported {
+                capability,
+                version,
+            } => write!(f, "{} is unsupported by server {}", capability, version),
+            Error::Timeout => write!(f, "request timed out"),
+            Error::Cancelled => write!(f, "request cancelled"),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "client")]
+            Error::Http(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// 用于 AsyncRead / AsyncWrite 等只能返回 io::Error 的接口
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Timeout => std::io::Error::new(std::io::ErrorKind::TimedOut, e),
+            Error::Cancelled => std::io::Error::new(std::io::ErrorKind::Interrupted, e),
+            e => std::io::Error::other(e),
+        }
+    }
+}
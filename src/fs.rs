@@ -1,51 +1,27 @@
-use super::{NullResponse, Response};
-use reqwest::Body;
+use super::{null_as_default, Error};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use tokio::fs::File;
-use tokio_util::codec::{BytesCodec, FramedRead};
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "client")]
+use {
+    super::{capability::Capability, client::ApiRequest, AlistClient},
+    reqwest::Method,
+    serde::de::DeserializeOwned,
+    serde_json::json,
+};
 
 /// 新建文件夹 POST /api/fs/mkdir
-pub async fn mkdir(server: &str, token: &str, path: &str) -> Result<(), String> {
-    let url = format!("{}/api/fs/mkdir", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Connection", "keep-alive")
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&json!({"path": path}))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
+#[cfg(feature = "client")]
+pub async fn mkdir(server: &str, token: &str, path: &str) -> Result<(), Error> {
+    AlistClient::new(server).with_token(token).mkdir(path).await
 }
 
 /// 重命名文件 POST /api/fs/rename
-pub async fn rename(server: &str, token: &str, path: &str, name: &str) -> Result<(), String> {
-    let url = format!("{}/api/fs/rename", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&json!({"path":path,"name":name}))
-        .send()
-        .await
-        .unwrap()
-        .json()
+#[cfg(feature = "client")]
+pub async fn rename(server: &str, token: &str, path: &str, name: &str) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .rename(path, name)
         .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 pub struct UploadParams {
@@ -58,49 +34,28 @@ pub struct UploadParams {
 }
 
 /// 流式上传文件 PUT /api/fs/put
-pub async fn upload(server: &str, token: &str, params: UploadParams) -> Result<(), String> {
-    let url = format!("{}/api/fs/put", server);
-    let file = match File::open(params.local_file).await {
-        Ok(file) => file,
-        Err(err) => {
-            return Err(err.to_string());
-        }
-    };
-    let filesize = file.metadata().await.unwrap().len();
-    let stream = FramedRead::new(file, BytesCodec::new());
-    let file_body = Body::wrap_stream(stream);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .put(url)
-        .header("Authorization", token)
-        .header(
-            "File-Path",
-            format!("{}/{}", params.remote_path, params.remote_name),
-        )
-        .header("Content-Length", filesize)
-        .header("Connection", "keep-alive")
-        .body(file_body)
-        .send()
-        .await
-        .unwrap()
-        .json()
+#[cfg(feature = "client")]
+pub async fn upload(server: &str, token: &str, params: UploadParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .upload(params)
         .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ListdirData {
+    // 空目录时服务端返回 null
+    #[serde(default, deserialize_with = "null_as_default")]
     pub content: Vec<DirFileInfo>,
     // 总数
     pub total: usize,
     // 说明
+    #[serde(default)]
     pub readme: String,
     // 是否可写入
+    #[serde(default)]
     pub write: bool,
+    #[serde(default)]
     pub provider: String,
 }
 
@@ -130,24 +85,12 @@ pub struct FileParams {
 }
 
 /// 列出文件目录 POST /api/fs/list
-pub async fn listdir(server: &str, token: &str, params: FileParams) -> Result<ListdirData, String> {
-    let url = format!("{}/api/fs/list", server);
-    let resp: Response<ListdirData> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+#[cfg(feature = "client")]
+pub async fn listdir(server: &str, token: &str, params: FileParams) -> Result<ListdirData, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .listdir(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(resp.data.unwrap())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -159,31 +102,122 @@ pub struct FileInfo {
     pub sign: String,
     pub thumb: String,
     pub r#type: isize,
+    #[serde(alias = "raw_url")]
     pub row_url: String,
+    #[serde(default)]
     pub readme: String,
+    #[serde(default)]
     pub provider: String,
-    pub related: Option<String>,
+    // 同目录下的相关文件, 如视频的字幕
+    // 服务端返回的是文件数组, 原先的 Option<String> 无法解析非空的 related
+    pub related: Option<Vec<DirFileInfo>>,
 }
 
 /// 获取某个文件/目录信息 POST /api/fs/get
-pub async fn fileinfo(server: &str, token: &str, params: FileParams) -> Result<FileInfo, String> {
-    let url = format!("{}/api/fs/get", server);
-    let resp: Response<FileInfo> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+#[cfg(feature = "client")]
+pub async fn fileinfo(server: &str, token: &str, params: FileParams) -> Result<FileInfo, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .fileinfo(params)
         .await
-        .unwrap()
-        .json()
+}
+
+/// 下载文件到本地, 使用 fs/get 返回的 raw_url
+#[cfg(feature = "client")]
+pub async fn download(
+    server: &str,
+    token: &str,
+    params: FileParams,
+    local_file: &str,
+) -> Result<u64, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .download(params, local_file)
         .await
-        .unwrap();
+}
 
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(resp.data.unwrap())
+/// 调用驱动的扩展方法 POST /api/fs/other
+///
+/// 使用 params 中的 path 和 password, 返回值结构由驱动决定
+#[cfg(feature = "client")]
+pub async fn other<Data: DeserializeOwned>(
+    server: &str,
+    token: &str,
+    params: FileParams,
+    method: &str,
+    data: Option<serde_json::Value>,
+) -> Result<Data, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .fs_other(params, method, data)
+        .await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoMeta {
+    // 时长(秒)
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoQuality {
+    // 清晰度, 如 LD SD HD FHD QHD
+    pub template_id: String,
+    #[serde(default)]
+    pub template_width: u32,
+    #[serde(default)]
+    pub template_height: u32,
+    // 转码状态, finished 时 url 可用
+    #[serde(default)]
+    pub status: String,
+    // m3u8 播放列表地址
+    #[serde(default)]
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoSubtitle {
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoPreview {
+    #[serde(default)]
+    pub category: String,
+    pub meta: Option<VideoMeta>,
+    #[serde(default, rename = "live_transcoding_task_list")]
+    pub qualities: Vec<VideoQuality>,
+    #[serde(default, rename = "live_transcoding_subtitle_task_list")]
+    pub subtitles: Vec<VideoSubtitle>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+struct VideoPreviewData {
+    video_preview_play_info: VideoPreview,
+}
+
+/// 获取视频转码播放信息, 仅阿里云盘等支持转码的驱动可用 POST /api/fs/other
+#[cfg(feature = "client")]
+pub async fn video_preview(
+    server: &str,
+    token: &str,
+    params: FileParams,
+) -> Result<VideoPreview, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .video_preview(params)
+        .await
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -204,6 +238,7 @@ pub struct SearchParams {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchFileData {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub content: Vec<SearchFileInfo>,
     pub total: usize,
 }
@@ -218,33 +253,22 @@ pub struct SearchFileInfo {
 }
 
 /// 搜索文件或文件夹 POST /api/fs/search
+#[cfg(feature = "client")]
 pub async fn search(
     server: &str,
     token: &str,
     params: SearchParams,
-) -> Result<SearchFileData, String> {
-    let url = format!("{}/api/fs/search", server);
-    let resp: Response<SearchFileData> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<SearchFileData, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .search(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(resp.data.unwrap())
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct GetDirParams {
-    // 搜索目录
+    // 搜索目录, 接口字段为 path
+    #[serde(rename = "path", alias = "parent")]
     pub parent: String,
     // 页数
     pub page: Option<usize>,
@@ -256,10 +280,30 @@ pub struct GetDirParams {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "DirsResponse")]
 pub struct SearchDirData {
     pub content: Vec<SearchDirInfo>,
 }
 
+// 服务端直接返回目录数组, 兼容旧的 {"content": [...]} 格式
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DirsResponse {
+    List(Option<Vec<SearchDirInfo>>),
+    Content { content: Option<Vec<SearchDirInfo>> },
+}
+
+impl From<DirsResponse> for SearchDirData {
+    fn from(resp: DirsResponse) -> Self {
+        let content = match resp {
+            DirsResponse::List(content) | DirsResponse::Content { content } => content,
+        };
+        SearchDirData {
+            content: content.unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchDirInfo {
     pub name: String,
@@ -267,28 +311,16 @@ pub struct SearchDirInfo {
 }
 
 /// 获取目录 POST /api/fs/dirs
+#[cfg(feature = "client")]
 pub async fn get_dirs(
     server: &str,
     token: &str,
     params: GetDirParams,
-) -> Result<SearchDirData, String> {
-    let url = format!("{}/api/fs/dirs", server);
-    let resp: Response<SearchDirData> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
+) -> Result<SearchDirData, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .get_dirs(params)
         .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(resp.data.unwrap())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -304,28 +336,16 @@ pub struct BatchRenameParams {
 }
 
 /// 批量重命名 POST /api/fs/batch_rename
+#[cfg(feature = "client")]
 pub async fn batch_rename(
     server: &str,
     token: &str,
     params: BatchRenameParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/batch_rename", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .batch_rename(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -341,28 +361,150 @@ pub struct BatchRegexRenameParams {
 }
 
 /// 正则重命名 POST /api/fs/regex_rename
+#[cfg(feature = "client")]
 pub async fn regex_rename(
     server: &str,
     token: &str,
     params: BatchRegexRenameParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/regex_rename", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .regex_rename(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RenameStatus {
+    // 正常重命名
+    Rename,
+    // 新旧名称相同, 无需重命名
+    NoOp,
+    // 与目录中已有文件或其它重命名结果重名
+    Collision,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenamePreview {
+    pub src_name: String,
+    pub new_name: String,
+    pub status: RenameStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegexRenamePlan {
+    pub src_dir: String,
+    // 仅包含被正则匹配到的文件
+    pub entries: Vec<RenamePreview>,
+}
+
+impl RegexRenamePlan {
+    pub fn has_collisions(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.status == RenameStatus::Collision)
+    }
+
+    /// 需要实际执行的重命名
+    pub fn renames(&self) -> impl Iterator<Item = &RenamePreview> {
+        self.entries
+            .iter()
+            .filter(|e| e.status == RenameStatus::Rename)
+    }
+}
+
+/// 在本地按 regex_rename 的规则计算重命名结果
+///
+/// 规则按顺序作用于每个文件的当前名称, 与服务端逐条执行的效果一致.
+/// 新名称与目录中其它文件(包括重命名前的名称)相同, 或多个文件得到同一新名称时标记为冲突.
+pub fn plan_regex_rename(
+    src_dir: &str,
+    names: &[String],
+    rules: &[RegexRenameParams],
+) -> Result<RegexRenamePlan, Error> {
+    let mut compiled = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let re =
+            regex::Regex::new(&rule.src_name_regex).map_err(|e| Error::Other(e.to_string()))?;
+        compiled.push((re, rule.new_name_regex.as_str()));
+    }
+
+    let mut entries = Vec::new();
+    for name in names {
+        let mut new_name = name.clone();
+        let mut matched = false;
+        for (re, replacement) in &compiled {
+            if re.is_match(&new_name) {
+                matched = true;
+                new_name = re.replace_all(&new_name, *replacement).into_owned();
+            }
+        }
+        if matched {
+            let status = if &new_name == name {
+                RenameStatus::NoOp
+            } else {
+                RenameStatus::Rename
+            };
+            entries.push(RenamePreview {
+                src_name: name.clone(),
+                new_name,
+                status,
+            });
+        }
+    }
 
-    if resp.code != 200 {
-        return Err(resp.message);
+    let existing: HashSet<&str> = names.iter().map(String::as_str).collect();
+    let renamed: HashMap<&str, &str> = entries
+        .iter()
+        .map(|e| (e.src_name.as_str(), e.new_name.as_str()))
+        .collect();
+    let mut final_names: HashMap<&str, usize> = HashMap::new();
+    for name in names {
+        let name = renamed.get(name.as_str()).copied().unwrap_or(name);
+        *final_names.entry(name).or_default() += 1;
     }
-    Ok(())
+    let collisions: Vec<bool> = entries
+        .iter()
+        .map(|e| {
+            e.status == RenameStatus::Rename
+                && (final_names[e.new_name.as_str()] > 1 || existing.contains(e.new_name.as_str()))
+        })
+        .collect();
+    for (entry, collision) in entries.iter_mut().zip(collisions) {
+        if collision {
+            entry.status = RenameStatus::Collision;
+        }
+    }
+
+    Ok(RegexRenamePlan {
+        src_dir: src_dir.to_string(),
+        entries,
+    })
+}
+
+/// 预览正则重命名结果, 不修改服务端文件
+#[cfg(feature = "client")]
+pub async fn preview_regex_rename(
+    server: &str,
+    token: &str,
+    params: BatchRegexRenameParams,
+) -> Result<RegexRenamePlan, Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .preview_regex_rename(params)
+        .await
+}
+
+/// 执行预览过的重命名计划, 存在冲突时拒绝执行
+#[cfg(feature = "client")]
+pub async fn apply_regex_rename(
+    server: &str,
+    token: &str,
+    plan: &RegexRenamePlan,
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .apply_regex_rename(plan)
+        .await
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -373,23 +515,12 @@ pub struct MoveParams {
 }
 
 /// 移动文件 POST /api/fs/move
-pub async fn move_file(server: &str, token: &str, params: MoveParams) -> Result<(), String> {
-    let url = format!("{}/api/fs/move", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
+#[cfg(feature = "client")]
+pub async fn move_file(server: &str, token: &str, params: MoveParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .move_file(params)
         .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -399,27 +530,16 @@ pub struct RecursiveMoveParams {
 }
 
 /// 聚合移动 POST /api/fs/recursive_move
+#[cfg(feature = "client")]
 pub async fn recursive_move(
     server: &str,
     token: &str,
     params: RecursiveMoveParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/recursive_move", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .recursive_move(params)
         .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -430,23 +550,12 @@ pub struct CopyParams {
 }
 
 /// 复制文件 POST /api/fs/copy
-pub async fn copy_file(server: &str, token: &str, params: CopyParams) -> Result<(), String> {
-    let url = format!("{}/api/fs/copy", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
+#[cfg(feature = "client")]
+pub async fn copy_file(server: &str, token: &str, params: CopyParams) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .copy_file(params)
         .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -456,51 +565,29 @@ pub struct DeleteParams {
 }
 
 /// 删除文件或文件夹 POST /api/fs/remove
+#[cfg(feature = "client")]
 pub async fn remove_directory(
     server: &str,
     token: &str,
     params: DeleteParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/remove", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .remove_directory(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 /// 删除空文件夹 POST /api/fs/remove_empty_directory
+#[cfg(feature = "client")]
 pub async fn remove_empty_directory(
     server: &str,
     token: &str,
     src_dir: String,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/remove_empty_directory", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&json!({"src_dir": src_dir}))
-        .send()
-        .await
-        .unwrap()
-        .json()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .remove_empty_directory(src_dir)
         .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -509,50 +596,309 @@ pub struct OfflineTaskParams {
     pub urls: Vec<String>,
 }
 
-/// 添加aria2下载 POST /api/fs/add_aria2
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletePolicy {
+    // 上传成功后删除临时文件
+    #[default]
+    DeleteOnUploadSucceed,
+    // 上传失败后删除临时文件
+    DeleteOnUploadFailed,
+    // 从不删除
+    DeleteNever,
+    // 总是删除
+    DeleteAlways,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OfflineDownloadParams {
+    #[serde(flatten)]
+    pub task: OfflineTaskParams,
+    // 下载工具, 可通过 public::offline_download_tools 获取
+    pub tool: String,
+    pub delete_policy: DeletePolicy,
+}
+
+/// 添加离线下载 POST /api/fs/add_offline_download
+#[cfg(feature = "client")]
+pub async fn add_offline_download(
+    server: &str,
+    token: &str,
+    params: OfflineDownloadParams,
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .add_offline_download(params)
+        .await
+}
+
+/// 添加aria2下载
+#[cfg(feature = "client")]
 pub async fn add_aria2_task(
     server: &str,
     token: &str,
     params: OfflineTaskParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/add_aria2", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .add_aria2_task(params)
         .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
 }
 
-/// 添加qBittorrent下载 POST /api/fs/add_qbit
+/// 添加qBittorrent下载
+#[cfg(feature = "client")]
 pub async fn add_qbit_task(
     server: &str,
     token: &str,
     params: OfflineTaskParams,
-) -> Result<(), String> {
-    let url = format!("{}/api/fs/add_qbit", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .post(url)
-        .header("Authorization", token)
-        .header("Content-Type", "application/json")
-        .json(&params)
-        .send()
+) -> Result<(), Error> {
+    AlistClient::new(server)
+        .with_token(token)
+        .add_qbit_task(params)
+        .await
+}
+
+#[cfg(feature = "client")]
+impl AlistClient {
+    /// 新建文件夹 POST /api/fs/mkdir
+    pub async fn mkdir(&self, path: &str) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/mkdir", json!({ "path": path })))
+            .await
+    }
+
+    /// 重命名文件 POST /api/fs/rename
+    pub async fn rename(&self, path: &str, name: &str) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post(
+            "/api/fs/rename",
+            json!({"path": path, "name": name}),
+        ))
+        .await
+    }
+
+    /// 流式上传文件 PUT /api/fs/put
+    pub async fn upload(&self, params: UploadParams) -> Result<(), Error> {
+        let req = ApiRequest::new(Method::PUT, "/api/fs/put")
+            .header(
+                "File-Path",
+                &format!("{}/{}", params.remote_path, params.remote_name),
+            )
+            .upload(&params.local_file);
+        self.call_unit(req).await
+    }
+
+    /// 列出文件目录 POST /api/fs/list
+    pub async fn listdir(&self, params: FileParams) -> Result<ListdirData, Error> {
+        let mut req = ApiRequest::post("/api/fs/list", json!(params));
+        // refresh 会让服务端重新获取存储的文件列表, 不自动重试
+        if params.refresh != Some(true) {
+            req = req.idempotent();
+        }
+        self.call(req).await
+    }
+
+    /// 获取某个文件/目录信息 POST /api/fs/get
+    pub async fn fileinfo(&self, params: FileParams) -> Result<FileInfo, Error> {
+        self.call(ApiRequest::post("/api/fs/get", json!(params)).idempotent())
+            .await
+    }
+
+    /// 下载文件到本地, 使用 fs/get 返回的 raw_url
+    pub async fn download(&self, params: FileParams, local_file: &str) -> Result<u64, Error> {
+        let info = self.fileinfo(params).await?;
+        if info.is_dir {
+            return Err(Error::Other(format!("{} is a directory", info.name)));
+        }
+        // raw_url 可能指向第三方存储, 不能携带 token
+        self.download_file("raw_url", &info.row_url, false, local_file)
+            .await
+    }
+
+    /// 调用驱动的扩展方法 POST /api/fs/other
+    pub async fn fs_other<Data: DeserializeOwned>(
+        &self,
+        params: FileParams,
+        method: &str,
+        data: Option<serde_json::Value>,
+    ) -> Result<Data, Error> {
+        self.call(ApiRequest::post(
+            "/api/fs/other",
+            json!({
+                "path": params.path,
+                "password": params.password,
+                "method": method,
+                "data": data,
+            }),
+        ))
+        .await
+    }
+
+    /// 获取视频转码播放信息 POST /api/fs/other
+    pub async fn video_preview(&self, params: FileParams) -> Result<VideoPreview, Error> {
+        let data: VideoPreviewData = self.fs_other(params, "video_preview", None).await?;
+        Ok(data.video_preview_play_info)
+    }
+
+    /// 搜索文件或文件夹 POST /api/fs/search
+    pub async fn search(&self, params: SearchParams) -> Result<SearchFileData, Error> {
+        self.call(ApiRequest::post("/api/fs/search", json!(params)).idempotent())
+            .await
+    }
+
+    /// 获取目录 POST /api/fs/dirs
+    pub async fn get_dirs(&self, params: GetDirParams) -> Result<SearchDirData, Error> {
+        self.call(ApiRequest::post("/api/fs/dirs", json!(params)).idempotent())
+            .await
+    }
+
+    /// 批量重命名 POST /api/fs/batch_rename
+    pub async fn batch_rename(&self, params: BatchRenameParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/batch_rename", json!(params)))
+            .await
+    }
+
+    /// 正则重命名 POST /api/fs/regex_rename
+    pub async fn regex_rename(&self, params: BatchRegexRenameParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/regex_rename", json!(params)))
+            .await
+    }
+
+    /// 预览正则重命名结果, 不修改服务端文件
+    pub async fn preview_regex_rename(
+        &self,
+        params: BatchRegexRenameParams,
+    ) -> Result<RegexRenamePlan, Error> {
+        let data = self
+            .listdir(FileParams {
+                path: Some(params.src_dir.clone()),
+                ..Default::default()
+            })
+            .await?;
+        let names: Vec<String> = data.content.into_iter().map(|f| f.name).collect();
+        plan_regex_rename(&params.src_dir, &names, &params.rename_objects)
+    }
+
+    /// 执行预览过的重命名计划, 存在冲突时拒绝执行
+    pub async fn apply_regex_rename(&self, plan: &RegexRenamePlan) -> Result<(), Error> {
+        if plan.has_collisions() {
+            return Err(Error::Other("rename plan has collisions".to_string()));
+        }
+        let rename_objects: Vec<RenameParams> = plan
+            .renames()
+            .map(|e| RenameParams {
+                src_name: e.src_name.clone(),
+                new_name: e.new_name.clone(),
+            })
+            .collect();
+        if rename_objects.is_empty() {
+            return Ok(());
+        }
+        self.batch_rename(BatchRenameParams {
+            src_dir: plan.src_dir.clone(),
+            rename_objects,
+        })
+        .await
+    }
+
+    /// 移动文件 POST /api/fs/move
+    pub async fn move_file(&self, params: MoveParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/move", json!(params)))
+            .await
+    }
+
+    /// 聚合移动 POST /api/fs/recursive_move
+    pub async fn recursive_move(&self, params: RecursiveMoveParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/recursive_move", json!(params)))
+            .await
+    }
+
+    /// 复制文件 POST /api/fs/copy
+    pub async fn copy_file(&self, params: CopyParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/copy", json!(params)))
+            .await
+    }
+
+    /// 删除文件或文件夹 POST /api/fs/remove
+    pub async fn remove_directory(&self, params: DeleteParams) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post("/api/fs/remove", json!(params)))
+            .await
+    }
+
+    /// 删除空文件夹 POST /api/fs/remove_empty_directory
+    pub async fn remove_empty_directory(&self, src_dir: String) -> Result<(), Error> {
+        self.call_unit(ApiRequest::post(
+            "/api/fs/remove_empty_directory",
+            json!({ "src_dir": src_dir }),
+        ))
+        .await
+    }
+
+    /// 添加离线下载 POST /api/fs/add_offline_download
+    ///
+    /// 旧版本服务端使用 add_aria2 / add_qbit, 仅支持 aria2 和 qBittorrent
+    pub async fn add_offline_download(&self, params: OfflineDownloadParams) -> Result<(), Error> {
+        let legacy = match params.tool.as_str() {
+            "aria2" => Some("/api/fs/add_aria2"),
+            "qBittorrent" => Some("/api/fs/add_qbit"),
+            // 其它工具旧版本不支持
+            _ => None,
+        };
+        let capabilities = self.capabilities();
+        if !capabilities.contains(Capability::OfflineDownload)
+            && capabilities.contains(Capability::LegacyOfflineDownload)
+        {
+            return match legacy {
+                Some(endpoint) => {
+                    self.call_unit(ApiRequest::post(endpoint, json!(params.task)))
+                        .await
+                }
+                None => self.require(Capability::OfflineDownload),
+            };
+        }
+        self.require(Capability::OfflineDownload)?;
+        let result = self
+            .call_unit(ApiRequest::post(
+                "/api/fs/add_offline_download",
+                json!(params),
+            ))
+            .await;
+        match (result, legacy) {
+            // 版本未知时无法预先判断, 旧版本服务端没有该接口, 改用旧接口
+            (Err(err), Some(endpoint)) if self.version().is_none() && is_missing_endpoint(&err) => {
+                self.call_unit(ApiRequest::post(endpoint, json!(params.task)))
+                    .await
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// 添加aria2下载
+    pub async fn add_aria2_task(&self, params: OfflineTaskParams) -> Result<(), Error> {
+        self.add_offline_download(OfflineDownloadParams {
+            task: params,
+            tool: "aria2".to_string(),
+            delete_policy: DeletePolicy::default(),
+        })
         .await
-        .unwrap()
-        .json()
+    }
+
+    /// 添加qBittorrent下载
+    pub async fn add_qbit_task(&self, params: OfflineTaskParams) -> Result<(), Error> {
+        self.add_offline_download(OfflineDownloadParams {
+            task: params,
+            tool: "qBittorrent".to_string(),
+            delete_policy: DeletePolicy::default(),
+        })
         .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
     }
-    Ok(())
+}
+
+/// 服务端没有该接口, 返回 HTTP 404 或 code 404,
+/// 旧版本服务端对未知接口返回前端页面 index.html, 状态码为 200
+#[cfg(feature = "client")]
+fn is_missing_endpoint(err: &Error) -> bool {
+    match err {
+        Error::Status(404) | Error::Api { code: 404, .. } => true,
+        Error::Decode(e) => e.is_syntax(),
+        _ => false,
+    }
 }
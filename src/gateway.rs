@@ -0,0 +1,79 @@
+//! WebDAV 和 S3 网关共用的 HTTP 服务和错误转换
+use super::Error;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::sync::oneshot;
+
+/// 在后台运行的 HTTP 服务, drop 时停止
+pub(crate) struct HttpServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl HttpServer {
+    /// 监听 addr, 端口为 0 时随机选择, 每个请求交给 handler 处理
+    pub(crate) fn start<F, Fut>(addr: SocketAddr, handler: F) -> Result<HttpServer, Error>
+    where
+        F: Fn(Request<Body>) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = Response<Body>> + Send + 'static,
+    {
+        let make_service = make_service_fn(move |_| {
+            let handler = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let resp = handler(req);
+                    async move { Ok::<_, Infallible>(resp.await) }
+                }))
+            }
+        });
+        let server = Server::try_bind(&addr)
+            .map_err(|e| Error::Other(format!("failed to listen on {}: {}", addr, e)))?
+            .serve(make_service);
+        let addr = server.local_addr();
+        let (tx, rx) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = rx.await;
+        }));
+        Ok(HttpServer {
+            addr,
+            shutdown: Some(tx),
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+/// 文件不存在, 服务端对不存在的文件返回 code 500 和 object not found
+pub(crate) fn is_not_found(err: &Error) -> bool {
+    match err {
+        Error::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+        Error::Api { code, message } => *code == 404 || message.contains("not found"),
+        _ => false,
+    }
+}
+
+/// 转义 xml 文本和属性中的特殊字符
+pub(crate) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,9 +1,55 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod admin;
+pub mod archive;
 pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod capability;
+#[cfg(feature = "client")]
+pub mod cassette;
+#[cfg(feature = "client")]
+pub mod chunker;
+#[cfg(feature = "client")]
+mod client;
+pub mod connection;
+#[cfg(feature = "crypt")]
+pub mod crypt;
+mod error;
 pub mod fs;
+#[cfg(any(feature = "webdav", feature = "s3"))]
+mod gateway;
+#[cfg(feature = "client")]
+pub mod metrics;
+#[cfg(feature = "client")]
+pub mod middleware;
+#[cfg(all(feature = "client", any(test, feature = "mock")))]
+pub mod mock;
+pub mod profile;
 pub mod public;
+#[cfg(feature = "client")]
+pub mod remote_file;
+#[cfg(feature = "client")]
+pub mod retry;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sign;
+#[cfg(feature = "client")]
+mod time;
+#[cfg(feature = "client")]
+pub mod timeout;
+#[cfg(feature = "client")]
+mod trace;
+#[cfg(feature = "client")]
+pub mod vfs;
+#[cfg(feature = "webdav")]
+pub mod webdav;
+
+#[cfg(feature = "client")]
+pub use client::AlistClient;
+pub use error::Error;
+#[cfg(feature = "client")]
+pub use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response<Data> {
@@ -12,62 +58,94 @@ pub struct Response<Data> {
     data: Option<Data>,
 }
 
+#[cfg(feature = "client")]
+impl<Data> Response<Data> {
+    /// code 不为 200 时转换为 Error::Api
+    pub(crate) fn check(self) -> Result<Option<Data>, Error> {
+        if self.code != 200 {
+            return Err(Error::Api {
+                code: self.code,
+                message: self.message,
+            });
+        }
+        Ok(self.data)
+    }
+
+    pub(crate) fn into_data(self) -> Result<Data, Error> {
+        self.check()?
+            .ok_or_else(|| Error::Other("response data is empty".to_string()))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NullResponse;
 
-#[cfg(test)]
+// 服务端可能用 null 表示空列表
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(all(test, feature = "client"))]
 mod tests {
     const SERVER: &str = "http://127.0.0.1:5244";
-    const USERNAME: &str = "admin";
-    const PASSWORD: &str = "123456";
 
     use super::*;
+    use mock::{Fault, MockServer};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn login(server: &MockServer) -> String {
+        auth::login(&server.url(), MockServer::USERNAME, MockServer::PASSWORD)
+            .await
+            .unwrap()
+    }
 
     #[tokio::test]
     async fn test_get_user_info() {
-        let token = auth::login(SERVER, USERNAME, PASSWORD).await.unwrap();
+        let server = MockServer::start().await;
+        let token = login(&server).await;
         println!("{token}");
-        match auth::get_user_info(SERVER, &token).await {
+        match auth::get_user_info(&server.url(), &token).await {
             Ok(user) => {
                 println!("{:?}", user);
-                assert!(true);
-            }
-            Err(e) => {
-                println!("{e}");
-                assert!(false);
+                assert_eq!(user.username, MockServer::USERNAME);
             }
+            Err(e) => panic!("{e}"),
         }
     }
 
     #[tokio::test]
     async fn test_mkdir() {
-        let token = auth::login(SERVER, USERNAME, PASSWORD).await.unwrap();
-        match fs::mkdir(SERVER, &token, "/cloud/test_mkdir").await {
-            Ok(()) => assert!(true),
-            Err(e) => {
-                println!("{e}");
-                assert!(false);
-            }
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        match fs::mkdir(&server.url(), &token, "/cloud/test_mkdir").await {
+            Ok(()) => assert!(server.exists("/cloud/test_mkdir")),
+            Err(e) => panic!("{e}"),
         }
     }
 
     #[tokio::test]
     async fn test_rename() {
-        let token = auth::login(SERVER, USERNAME, PASSWORD).await.unwrap();
-        match fs::rename(SERVER, &token, "/cloud/test_mkdir", "test_rename").await {
-            Ok(()) => assert!(true),
-            Err(e) => {
-                println!("{e}");
-                assert!(false);
-            }
+        let server = MockServer::start().await;
+        server.add_dir("/cloud/test_mkdir");
+        let token = login(&server).await;
+        match fs::rename(&server.url(), &token, "/cloud/test_mkdir", "test_rename").await {
+            Ok(()) => assert!(server.exists("/cloud/test_rename")),
+            Err(e) => panic!("{e}"),
         }
     }
 
     #[tokio::test]
     async fn test_upload() {
-        let token = auth::login(SERVER, USERNAME, PASSWORD).await.unwrap();
+        let server = MockServer::start().await;
+        server.add_dir("/cloud/test_rename");
+        let token = login(&server).await;
         match fs::upload(
-            SERVER,
+            &server.url(),
             &token,
             fs::UploadParams {
                 local_file: ".gitignore".to_string(),
@@ -77,28 +155,1718 @@ mod tests {
         )
         .await
         {
-            Ok(()) => assert!(true),
-            Err(e) => {
-                println!("{e}");
-                assert!(false);
-            }
+            Ok(()) => assert_eq!(
+                server
+                    .read_file("/cloud/test_rename/gitignore.txt")
+                    .unwrap(),
+                std::fs::read(".gitignore").unwrap()
+            ),
+            Err(e) => panic!("{e}"),
         }
     }
 
     #[tokio::test]
     async fn test_listdir() {
-        let token = auth::login(SERVER, USERNAME, PASSWORD).await.unwrap();
-        let mut params = fs::FileParams::default();
-        params.path = Some("/cloud/test_rename".to_string());
-        match fs::listdir(SERVER, &token, params).await {
+        let server = MockServer::start().await;
+        server.add_file("/cloud/test_rename/gitignore.txt", b"target/");
+        let token = login(&server).await;
+        let params = fs::FileParams {
+            path: Some("/cloud/test_rename".to_string()),
+            ..Default::default()
+        };
+        match fs::listdir(&server.url(), &token, params).await {
             Ok(n) => {
                 println!("{:?}", n);
                 assert_eq!(n.total, 1);
             }
-            Err(e) => {
-                println!("{e}");
-                assert!(false);
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_dirs() {
+        let server = MockServer::start().await;
+        server.add_dir("/cloud/show/S01");
+        server.add_file("/cloud/show/notes.txt", b"");
+        let token = login(&server).await;
+        let params = fs::GetDirParams {
+            parent: "/cloud/show".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&params).unwrap()["path"],
+            "/cloud/show"
+        );
+        let dirs = fs::get_dirs(&server.url(), &token, params).await.unwrap();
+        let names: Vec<_> = dirs.content.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["S01"]);
+    }
+
+    #[tokio::test]
+    async fn test_mock_faults() {
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        let params = || fs::FileParams {
+            path: Some("/".to_string()),
+            ..Default::default()
+        };
+
+        server.inject(Fault::Unauthorized);
+        let err = fs::listdir(&server.url(), &token, params())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(401));
+
+        server.inject(Fault::MalformedJson);
+        let err = fs::listdir(&server.url(), &token, params())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Decode(_)));
+
+        // 只读请求默认重试
+        server.inject(Fault::ServerError);
+        let requests = server.requests();
+        assert!(fs::listdir(&server.url(), &token, params()).await.is_ok());
+        assert_eq!(server.requests(), requests + 2);
+
+        let err = fs::listdir(&server.url(), "bad-token", params())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(401));
+
+        assert!(fs::listdir(&server.url(), &token, params()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cassette_replay() {
+        // 按各版本的响应结构手写, 不是实际录制的, 只检查能否解析这些结构
+        for version in ["v3.11.0", "v3.42.0"] {
+            let path = format!("tests/fixtures/synthetic/{}.json", version);
+            let cassette = Arc::new(cassette::Cassette::replay(&path).unwrap());
+            let mut client = AlistClient::new(SERVER).with_cassette(cassette);
+            let file = |path: &str| fs::FileParams {
+                path: Some(path.to_string()),
+                ..Default::default()
+            };
+
+            client.ping().await.unwrap();
+            let settings = client.get_settings().await.unwrap();
+            assert_eq!(settings.version, version);
+            client.login("admin", "password").await.unwrap();
+            assert_eq!(client.get_user_info().await.unwrap().username, "admin");
+
+            let root = client.listdir(file("/")).await.unwrap();
+            assert_eq!(root.total, root.content.len());
+            let empty = client.listdir(file("/empty")).await.unwrap();
+            assert!(empty.content.is_empty());
+            let info = client.fileinfo(file("/cloud/movie.mkv")).await.unwrap();
+            assert!(info.row_url.contains("/p/cloud/movie.mkv"));
+            let related = info.related.unwrap_or_default();
+            assert_eq!(related.len(), if version == "v3.42.0" { 1 } else { 0 });
+            let dirs = client
+                .get_dirs(fs::GetDirParams {
+                    parent: "/".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(dirs.content[0].name, "cloud");
+            let found = client
+                .search(fs::SearchParams {
+                    parent: "/".to_string(),
+                    keywords: "nothing".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(found.total, 0);
+            assert_eq!(client.get_setting("token").await.unwrap().key, "token");
+            let err = client.listdir(file("/missing")).await.unwrap_err();
+            assert_eq!(err.code(), Some(500));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cassette_record() {
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"hello");
+        let path =
+            std::env::temp_dir().join(format!("alistapi-cassette-{}.json", std::process::id()));
+        let cassette = Arc::new(cassette::Cassette::record(&path));
+        let mut client = AlistClient::new(&server.url()).with_cassette(cassette.clone());
+        client
+            .login(MockServer::USERNAME, MockServer::PASSWORD)
+            .await
+            .unwrap();
+        let params = || fs::FileParams {
+            path: Some("/cloud".to_string()),
+            ..Default::default()
+        };
+        let recorded = client.listdir(params()).await.unwrap();
+        client.ping().await.unwrap();
+        cassette.save().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(client.token()));
+        assert!(!content.contains(&auth::sha256(MockServer::PASSWORD)));
+
+        let cassette = Arc::new(cassette::Cassette::replay(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let mut client = AlistClient::new(&server.url()).with_cassette(cassette);
+        client.login("admin", "wrong password").await.unwrap();
+        let replayed = client.listdir(params()).await.unwrap();
+        assert_eq!(replayed.content[0].name, recorded.content[0].name);
+        client.ping().await.unwrap();
+        // 每条记录只回放一次
+        assert!(client.ping().await.is_err());
+        assert_eq!(server.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        let policy = retry::RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let client = AlistClient::new(&server.url())
+            .with_token(&token)
+            .with_retry(policy.clone());
+        let params = || fs::FileParams {
+            path: Some("/".to_string()),
+            ..Default::default()
+        };
+
+        for _ in 0..policy.max_attempts {
+            server.inject(Fault::ServerError);
+        }
+        let err = client.listdir(params()).await.unwrap_err();
+        assert!(matches!(err, Error::Status(500)));
+
+        // 修改服务端的请求默认不重试
+        server.inject(Fault::ServerError);
+        let requests = server.requests();
+        assert!(client.mkdir("/cloud").await.is_err());
+        assert_eq!(server.requests(), requests + 1);
+        assert!(!server.exists("/cloud"));
+
+        // 刷新存储的 listdir 也不重试
+        server.inject(Fault::ServerError);
+        let requests = server.requests();
+        let refresh = fs::FileParams {
+            refresh: Some(true),
+            ..params()
+        };
+        assert!(client.listdir(refresh).await.is_err());
+        assert_eq!(server.requests(), requests + 1);
+
+        let client = client.with_retry(retry::RetryPolicy {
+            retry_mutating: true,
+            ..policy
+        });
+        server.inject_for("/api/fs/put", Fault::ServerError);
+        client
+            .upload(fs::UploadParams {
+                local_file: ".gitignore".to_string(),
+                remote_path: "/cloud".to_string(),
+                remote_name: "gitignore.txt".to_string(),
+            })
+            .await
+            .unwrap();
+        // 重试时重新读取文件
+        assert_eq!(
+            server.read_file("/cloud/gitignore.txt").unwrap(),
+            std::fs::read(".gitignore").unwrap()
+        );
+
+        // 超时的请求可能已经执行, 即使 retry_mutating 也不重试
+        let client = client.with_timeouts(timeout::Timeouts {
+            total: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        server.inject(Fault::Delay(Duration::from_millis(300)));
+        let requests = server.requests();
+        let err = client.mkdir("/cloud/slow").await.unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+        assert_eq!(server.requests(), requests + 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_and_cancel() {
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"hello");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url())
+            .with_token(&token)
+            .with_retry(retry::RetryPolicy::none());
+        let params = || fs::FileParams {
+            path: Some("/cloud/a.txt".to_string()),
+            ..Default::default()
+        };
+
+        let timeouts = timeout::Timeouts {
+            total: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        server.inject(Fault::Delay(Duration::from_millis(500)));
+        let err = client
+            .clone()
+            .with_timeouts(timeouts)
+            .fileinfo(params())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+        assert!(client.fileinfo(params()).await.is_ok());
+        // 单次调用的 read 超时在请求时生效, connect 不同也沿用原来的连接池
+        server.inject(Fault::Delay(Duration::from_millis(500)));
+        let err = client
+            .clone()
+            .with_timeouts(timeout::Timeouts {
+                connect: Some(Duration::from_secs(1)),
+                read: Some(Duration::from_millis(100)),
+                total: None,
+            })
+            .fileinfo(params())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+
+        let local_file =
+            std::env::temp_dir().join(format!("alistapi-cancel-{}", std::process::id()));
+        let local_file = local_file.to_str().unwrap();
+        let cancel = CancellationToken::new();
+        server.inject_for("/d/", Fault::Delay(Duration::from_secs(5)));
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+        let err = client
+            .clone()
+            .with_cancel(cancel)
+            .download(params(), local_file)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Cancelled));
+        assert!(!std::path::Path::new(local_file).exists());
+
+        assert_eq!(client.download(params(), local_file).await.unwrap(), 5);
+        std::fs::remove_file(local_file).unwrap();
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing_spans() {
+        use std::sync::Mutex;
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_ansi(false)
+            .with_writer(move || Captured(writer.clone()))
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"hello");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        client
+            .listdir(fs::FileParams {
+                path: Some("/cloud".to_string()),
+                password: Some("dir-secret".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let local_file =
+            std::env::temp_dir().join(format!("alistapi-trace-{}", std::process::id()));
+        let local_file = local_file.to_str().unwrap();
+        let params = fs::FileParams {
+            path: Some("/cloud/a.txt".to_string()),
+            ..Default::default()
+        };
+        client.download(params, local_file).await.unwrap();
+        std::fs::remove_file(local_file).unwrap();
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("endpoint=\"/api/fs/list\""));
+        assert!(output.contains("path=\"/cloud\""));
+        assert!(output.contains("status=200"));
+        assert!(output.contains("code=200"));
+        assert!(output.contains("bytes_received=5"));
+        assert!(output.contains("duration_ms="));
+        assert!(!output.contains(&token));
+        assert!(!output.contains("dir-secret"));
+        assert!(!output.contains(&auth::sha256(MockServer::PASSWORD)));
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"hello metrics");
+        server.add_file("/cloud/b.txt", b"");
+        let metrics = Arc::new(metrics::Metrics::new());
+        let mut client = AlistClient::new(&server.url())
+            .with_metrics(metrics.clone())
+            .with_retry(retry::RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..Default::default()
+            });
+        client
+            .login(MockServer::USERNAME, MockServer::PASSWORD)
+            .await
+            .unwrap();
+        assert_eq!(metrics.token_refreshes(), 1);
+
+        let params = || fs::FileParams {
+            path: Some("/cloud".to_string()),
+            ..Default::default()
+        };
+        server.inject(Fault::ServerError);
+        client.listdir(params()).await.unwrap();
+        assert!(client.rename("/cloud/a.txt", "b.txt").await.is_err());
+        let stats = metrics.endpoint("/api/fs/list");
+        assert_eq!((stats.requests, stats.retries), (1, 1));
+
+        let local = std::env::temp_dir().join(format!("alist-metrics-{}", std::process::id()));
+        let local = local.to_str().unwrap();
+        let written = client
+            .download(
+                fs::FileParams {
+                    path: Some("/cloud/a.txt".to_string()),
+                    ..Default::default()
+                },
+                local,
+            )
+            .await
+            .unwrap();
+        std::fs::remove_file(local).unwrap();
+        assert_eq!(metrics.downloaded_bytes(), written);
+        // 随机读取和 open_read 同样计入下载量
+        {
+            use tokio::io::AsyncReadExt;
+            use vfs::RemoteFs;
+
+            let mut content = Vec::new();
+            let mut file = client.open("/cloud/a.txt").await.unwrap();
+            file.read_to_end(&mut content).await.unwrap();
+            let mut reader = client.open_read("/cloud/a.txt").await.unwrap();
+            reader.read_to_end(&mut content).await.unwrap();
+            assert_eq!(content, b"hello metricshello metrics");
+        }
+        assert_eq!(metrics.downloaded_bytes(), written * 3);
+        client
+            .upload(fs::UploadParams {
+                local_file: ".gitignore".to_string(),
+                remote_path: "/cloud".to_string(),
+                remote_name: "gitignore.txt".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            metrics.uploaded_bytes(),
+            std::fs::metadata(".gitignore").unwrap().len()
+        );
+
+        let text = metrics.render_prometheus();
+        println!("{text}");
+        assert!(text.contains("alist_requests_total{endpoint=\"/api/fs/list\",code=\"200\"} 1"));
+        assert!(text.contains("alist_requests_total{endpoint=\"/api/fs/rename\",code=\"403\"} 1"));
+        assert!(text.contains("alist_requests_total{endpoint=\"raw_url\",code=\"200\"} 1"));
+        assert!(text.contains("alist_retries_total{endpoint=\"/api/fs/list\"} 1"));
+        assert!(text.contains("alist_request_duration_seconds_count{endpoint=\"/api/fs/put\"} 1"));
+        assert!(text.contains("alist_token_refreshes_total 1"));
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        use middleware::{
+            ApiRequest, ApiResponse, BoxFuture, Middleware, Next, RawRequest, SetHeaders,
+        };
+        use std::sync::Mutex;
+        use tokio::io::AsyncReadExt;
+
+        // 记录经过的请求, 第一次请求 fs/list 时返回 503
+        #[derive(Default)]
+        struct Recorder {
+            seen: Mutex<Vec<(String, Option<String>)>>,
+        }
+
+        impl Middleware for Recorder {
+            fn handle<'a>(
+                &'a self,
+                req: ApiRequest,
+                next: Next<'a>,
+            ) -> BoxFuture<'a, Result<ApiResponse, Error>> {
+                Box::pin(async move {
+                    let header = req
+                        .headers
+                        .iter()
+                        .find(|(k, _)| k == "X-Proxy-Auth")
+                        .map(|(_, v)| v.clone());
+                    let first = {
+                        let mut seen = self.seen.lock().unwrap();
+                        seen.push((req.endpoint.clone(), header));
+                        seen.len() == 1
+                    };
+                    if first && req.endpoint == "/api/fs/list" {
+                        return Ok(ApiResponse {
+                            status: 503,
+                            body: Vec::new(),
+                        });
+                    }
+                    next.run(req).await
+                })
+            }
+
+            fn prepare_raw(&self, req: &mut RawRequest) -> Result<(), Error> {
+                let header = req
+                    .headers
+                    .iter()
+                    .find(|(k, _)| k == "X-Proxy-Auth")
+                    .map(|(_, v)| v.clone());
+                self.seen.lock().unwrap().push((req.url.clone(), header));
+                if req.url.ends_with("/blocked.txt") {
+                    return Err(Error::Other("blocked".to_string()));
+                }
+                Ok(())
+            }
+        }
+
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        let recorder = Arc::new(Recorder::default());
+        let client = AlistClient::new(&server.url())
+            .with_token(&token)
+            .with_retry(retry::RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                ..Default::default()
+            })
+            .with_middleware(Arc::new(SetHeaders::new().header("X-Proxy-Auth", "secret")))
+            .with_middleware(recorder.clone());
+
+        let requests = server.requests();
+        let data = client
+            .listdir(fs::FileParams {
+                path: Some("/".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(data.total, 0);
+        // 第一次请求被中间件拦截, 重试后才到达服务端
+        assert_eq!(server.requests(), requests + 1);
+        let seen = recorder.seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert!(seen
+            .iter()
+            .all(|(e, h)| e == "/api/fs/list" && h.as_deref() == Some("secret")));
+
+        // 下载和 Range 读取调用 prepare_raw
+        server.add_file("/cloud/a.txt", b"hello");
+        server.add_file("/cloud/blocked.txt", b"secret");
+        recorder.seen.lock().unwrap().clear();
+        let url = format!("{}/d/cloud/a.txt", server.url());
+        let mut file = client.open("/cloud/a.txt").await.unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "hello");
+        let seen = recorder.seen.lock().unwrap().clone();
+        assert!(seen
+            .iter()
+            .any(|(u, h)| u.starts_with(&url) && h.as_deref() == Some("secret")));
+
+        let requests = server.requests();
+        let err = client
+            .download(
+                fs::FileParams {
+                    path: Some("/cloud/blocked.txt".to_string()),
+                    ..Default::default()
+                },
+                "/nonexistent/blocked.txt",
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "blocked");
+        // 只发送了 fs/get, 下载请求被拒绝
+        assert_eq!(server.requests(), requests + 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_config() {
+        use connection::{ConnectionConfig, ProxyConfig};
+
+        // 通过代理访问时, 服务端地址只需要代理能解析
+        let server = MockServer::start().await;
+        let client = AlistClient::new("http://alist.invalid")
+            .with_connection(ConnectionConfig {
+                proxy: Some(ProxyConfig::new(&server.url())),
+                ..Default::default()
+            })
+            .unwrap();
+        client.ping().await.unwrap();
+
+        let err = AlistClient::new(&server.url())
+            .with_connection(ConnectionConfig {
+                root_certificates: vec!["/nonexistent/ca.pem".into()],
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/ca.pem"));
+
+        let config = profile::Config::parse(
+            r#"
+            [profiles.office]
+            server = "https://alist.corp.example.com"
+
+            [profiles.office.connection]
+            accept_invalid_certs = true
+            proxy = { url = "http://proxy:8080", username = "me", password = { value = "pw" } }
+            "#,
+        )
+        .unwrap();
+        let (_, office) = config.profile(Some("office")).unwrap();
+        let proxy = office.connection.proxy.as_ref().unwrap();
+        assert_eq!(proxy.username.as_deref(), Some("me"));
+        assert!(office.connection.accept_invalid_certs);
+        AlistClient::new(&office.server)
+            .with_connection(office.connection.clone())
+            .unwrap();
+
+        // 修改超时时复用已获取的代理密码, 不再执行命令
+        let counter = std::env::temp_dir().join(format!("alistapi-proxy-{}", std::process::id()));
+        let mut proxy = ProxyConfig::new(&server.url());
+        proxy.username = Some("me".to_string());
+        proxy.password = Some(profile::Secret::Command(format!(
+            "echo x >> {} && echo pw",
+            counter.display()
+        )));
+        let client = AlistClient::new("http://alist.invalid")
+            .with_connection(ConnectionConfig {
+                proxy: Some(proxy),
+                ..Default::default()
+            })
+            .unwrap()
+            .with_timeouts(timeout::Timeouts {
+                connect: Some(Duration::from_secs(5)),
+                ..Default::default()
+            });
+        client.ping().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&counter).unwrap(), "x\n");
+        std::fs::remove_file(&counter).unwrap();
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking_client() {
+        // 模拟服务端运行在单独的运行时中
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(MockServer::start());
+
+        let mut client = blocking::AlistClient::new(&server.url()).unwrap();
+        client.ping().unwrap();
+        client
+            .login(MockServer::USERNAME, MockServer::PASSWORD)
+            .unwrap();
+        assert_eq!(
+            client.get_user_info().unwrap().username,
+            MockServer::USERNAME
+        );
+
+        client.mkdir("/cloud").unwrap();
+        client
+            .upload(fs::UploadParams {
+                local_file: ".gitignore".to_string(),
+                remote_path: "/cloud".to_string(),
+                remote_name: "gitignore.txt".to_string(),
+            })
+            .unwrap();
+        // 在其它线程中使用同一个客户端
+        let worker = client.clone();
+        let data = std::thread::spawn(move || {
+            worker.listdir(fs::FileParams {
+                path: Some("/cloud".to_string()),
+                ..Default::default()
+            })
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+        assert_eq!(data.content.len(), 1);
+        assert_eq!(data.content[0].name, "gitignore.txt");
+    }
+
+    #[tokio::test]
+    async fn test_remote_fs() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use vfs::{LocalFs, RemoteFs};
+
+        // 在本地目录和 alist 上执行相同的操作, 结果应该一致
+        async fn exercise(fs: &dyn RemoteFs) -> Vec<(String, bool, u64)> {
+            fs.create_dir("/work/sub").await.unwrap();
+            let mut writer = fs.create_write("/work/a.txt").await.unwrap();
+            writer.write_all(b"hello remote fs").await.unwrap();
+            writer.shutdown().await.unwrap();
+
+            let mut content = String::new();
+            let mut reader = fs.open_read("/work/a.txt").await.unwrap();
+            reader.read_to_string(&mut content).await.unwrap();
+            assert_eq!(content, "hello remote fs");
+
+            // 源目录中的同名文件不能被跨目录重命名覆盖
+            let mut writer = fs.create_write("/work/b.txt").await.unwrap();
+            writer.write_all(b"keep").await.unwrap();
+            writer.shutdown().await.unwrap();
+
+            fs.copy("/work/a.txt", "/work/sub").await.unwrap();
+            fs.rename("/work/a.txt", "/work/sub/b.txt").await.unwrap();
+            fs.rename("/work/sub/a.txt", "/work/c.txt").await.unwrap();
+            let meta = fs.metadata("/work/sub/b.txt").await.unwrap();
+            assert_eq!((meta.is_dir, meta.size), (false, 15));
+            assert!(meta.modified.is_some());
+            fs.create_dir("/work/empty").await.unwrap();
+            fs.remove("/work/empty").await.unwrap();
+            assert!(fs.metadata("/work/empty").await.is_err());
+
+            let mut listing = Vec::new();
+            for dir in ["/work", "/work/sub"] {
+                for m in fs.read_dir(dir).await.unwrap() {
+                    listing.push((format!("{}/{}", dir, m.name), m.is_dir, m.size));
+                }
             }
+            listing.sort();
+            listing
+        }
+
+        let root = std::env::temp_dir().join(format!("alist-vfs-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let local = exercise(&LocalFs::new(&root)).await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let remote = exercise(&client).await;
+        assert_eq!(local, remote);
+        assert_eq!(
+            remote,
+            vec![
+                ("/work/b.txt".to_string(), false, 4),
+                ("/work/c.txt".to_string(), false, 15),
+                ("/work/sub".to_string(), true, 0),
+                ("/work/sub/b.txt".to_string(), false, 15),
+            ]
+        );
+
+        // 移动失败时改回原来的名称
+        server.inject_for("/api/fs/move", Fault::ServerError);
+        assert!(RemoteFs::rename(&client, "/work/c.txt", "/work/sub/d.txt")
+            .await
+            .is_err());
+        assert!(server.exists("/work/c.txt"));
+        assert_eq!(client.read_dir("/work").await.unwrap().len(), 3);
+
+        assert!(LocalFs::new("/tmp")
+            .metadata("/../etc/passwd")
+            .await
+            .is_err());
+        // 符号链接不能指向 root 以外
+        #[cfg(unix)]
+        {
+            let root = std::env::temp_dir().join(format!("alist-vfs-link-{}", std::process::id()));
+            std::fs::create_dir_all(&root).unwrap();
+            std::os::unix::fs::symlink("/etc", root.join("etc")).unwrap();
+            std::os::unix::fs::symlink("/nonexistent", root.join("dangling")).unwrap();
+            std::os::unix::fs::symlink(".", root.join("self")).unwrap();
+            let fs = LocalFs::new(&root);
+            assert!(fs.metadata("/etc/passwd").await.is_err());
+            assert!(fs.read_dir("/etc").await.is_err());
+            assert!(fs.create_write("/dangling").await.is_err());
+            assert!(fs.create_dir("/self/sub").await.is_ok());
+            std::fs::remove_dir_all(&root).unwrap();
         }
+        assert_eq!(
+            time::parse_rfc3339("2024-01-02T11:04:05.5+08:00"),
+            Some(std::time::UNIX_EPOCH + Duration::from_millis(1704164645500))
+        );
+        assert_eq!(time::parse_rfc3339("0001-01-01T00:00:00Z"), None);
+    }
+
+    #[tokio::test]
+    async fn test_remote_file() {
+        use std::io::SeekFrom;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let server = MockServer::start().await;
+        let content: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        server.add_file("/cloud/big.bin", &content);
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let mut file = client
+            .open_with(
+                "/cloud/big.bin",
+                remote_file::OpenOptions { read_ahead: 100 },
+            )
+            .await
+            .unwrap();
+        assert_eq!(file.len(), 1000);
+
+        let mut chunk = [0u8; 10];
+        file.seek(SeekFrom::Start(500)).await.unwrap();
+        file.read_exact(&mut chunk).await.unwrap();
+        assert_eq!(&chunk[..], &content[500..510]);
+        // 预读范围内的顺序读取不再发送请求
+        let requests = server.requests();
+        file.read_exact(&mut chunk).await.unwrap();
+        assert_eq!(&chunk[..], &content[510..520]);
+        assert_eq!(server.requests(), requests);
+
+        file.seek(SeekFrom::End(-5)).await.unwrap();
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).await.unwrap();
+        assert_eq!(tail, &content[995..]);
+        assert!(file.seek(SeekFrom::Current(-2000)).await.is_err());
+
+        // 签名过期后重新获取 raw_url
+        server.inject_for("/d/", Fault::LinkExpired);
+        let requests = server.requests();
+        file.seek(SeekFrom::Start(42)).await.unwrap();
+        file.read_exact(&mut chunk).await.unwrap();
+        assert_eq!(&chunk[..], &content[42..52]);
+        assert_eq!(server.requests(), requests + 3);
+
+        let mut all = Vec::new();
+        file.rewind().await.unwrap();
+        server.inject_for("/d/", Fault::LinkExpired);
+        file.read_to_end(&mut all).await.unwrap();
+        assert_eq!(all, content);
+
+        // 过期链接返回的错误信息不会写入本地文件
+        let local = std::env::temp_dir().join(format!("alist-expired-{}", std::process::id()));
+        let local_str = local.to_str().unwrap();
+        server.inject_for("/d/", Fault::LinkExpired);
+        let params = || fs::FileParams {
+            path: Some("/cloud/big.bin".to_string()),
+            ..Default::default()
+        };
+        let err = client.download(params(), local_str).await.unwrap_err();
+        assert_eq!(err.code(), Some(401));
+        assert!(!local.exists());
+        // 内容为 json 的文件仍正常下载
+        let data = br#"{"name": "config"}"#;
+        server.add_file("/cloud/data.json", data);
+        let params = fs::FileParams {
+            path: Some("/cloud/data.json".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(client.download(params, local_str).await.unwrap(), 18);
+        assert_eq!(std::fs::read(&local).unwrap(), data);
+        let _ = std::fs::remove_file(&local);
+    }
+
+    #[cfg(feature = "webdav")]
+    #[tokio::test]
+    async fn test_webdav_gateway() {
+        use reqwest::Method;
+
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a b.txt", b"hello webdav");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let dav = webdav::WebDavServer::start(Arc::new(client), ([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap();
+        let http = reqwest::Client::new();
+        let request = |method: &str, path: &str| {
+            http.request(
+                Method::from_bytes(method.as_bytes()).unwrap(),
+                format!("{}{}", dav.url(), path),
+            )
+        };
+
+        let resp = request("PROPFIND", "/cloud")
+            .header("Depth", "1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 207);
+        let xml = resp.text().await.unwrap();
+        assert!(xml.contains("<D:href>/cloud/</D:href>"));
+        assert!(xml.contains("<D:href>/cloud/a%20b.txt</D:href>"));
+        assert!(xml.contains("<D:getcontentlength>12</D:getcontentlength>"));
+
+        let resp = request("GET", "/cloud/a%20b.txt").send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().await.unwrap(), "hello webdav");
+
+        let status = |resp: reqwest::Response| resp.status().as_u16();
+        assert_eq!(
+            status(request("MKCOL", "/cloud/new").send().await.unwrap()),
+            201
+        );
+        assert_eq!(
+            status(request("MKCOL", "/cloud/new").send().await.unwrap()),
+            405
+        );
+        let resp = request("PUT", "/cloud/new/c.txt")
+            .body("uploaded")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status(resp), 201);
+        assert_eq!(server.read_file("/cloud/new/c.txt").unwrap(), b"uploaded");
+
+        // 目标目录中与源文件同名的文件不受影响
+        server.add_file("/cloud/new/a b.txt", b"keep");
+        let dest = |path: &str| format!("{}{}", dav.url(), path);
+        let resp = request("COPY", "/cloud/a%20b.txt")
+            .header("Destination", dest("/cloud/new/d.txt"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status(resp), 201);
+        assert_eq!(
+            server.read_file("/cloud/new/d.txt").unwrap(),
+            b"hello webdav"
+        );
+        assert!(server.exists("/cloud/a b.txt"));
+        assert_eq!(server.read_file("/cloud/new/a b.txt").unwrap(), b"keep");
+
+        let resp = request("MOVE", "/cloud/new/c.txt")
+            .header("Destination", dest("/cloud/new/d.txt"))
+            .header("Overwrite", "F")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status(resp), 412);
+        let resp = request("MOVE", "/cloud/new/c.txt")
+            .header("Destination", dest("/cloud/e.txt"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status(resp), 201);
+        assert!(!server.exists("/cloud/new/c.txt"));
+        assert_eq!(server.read_file("/cloud/e.txt").unwrap(), b"uploaded");
+
+        assert_eq!(
+            status(request("DELETE", "/cloud/new").send().await.unwrap()),
+            204
+        );
+        assert!(!server.exists("/cloud/new/d.txt"));
+        assert_eq!(
+            status(request("DELETE", "/cloud/new").send().await.unwrap()),
+            404
+        );
+        assert_eq!(
+            status(request("GET", "/cloud/missing").send().await.unwrap()),
+            404
+        );
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_s3_gateway() {
+        use std::collections::BTreeMap;
+
+        let server = MockServer::start().await;
+        server.add_file("/cloud/data/a.txt", b"hello s3 gateway");
+        server.add_file("/cloud/data/logs/b 1.log", b"log");
+        server.add_file("/cloud/data/archive/2023/c.log", b"old");
+        server.add_file("/cloud/other.txt", b"outside");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let buckets = BTreeMap::from([("data".to_string(), "/cloud/data".to_string())]);
+        let s3 = s3::S3Server::start(client, buckets, ([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap();
+        let http = reqwest::Client::new();
+        let url = |path: &str| format!("{}{}", s3.url(), path);
+
+        let xml = http
+            .get(url("/"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<Name>data</Name>"));
+
+        let xml = http
+            .get(url("/data?list-type=2&delimiter=%2F"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<Key>a.txt</Key>"));
+        assert!(xml.contains("<Size>16</Size>"));
+        assert!(xml.contains("<CommonPrefixes><Prefix>logs/</Prefix></CommonPrefixes>"));
+        assert!(!xml.contains("b 1.log"));
+        let xml = http
+            .get(url("/data?list-type=2&prefix=lo&encoding-type=url"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<Key>logs/b%201.log</Key>"));
+        assert!(!xml.contains("a.txt"));
+        let xml = http
+            .get(url("/data?list-type=2&max-keys=1"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<IsTruncated>true</IsTruncated>"));
+        assert!(xml.contains("<NextContinuationToken>a.txt</NextContinuationToken>"));
+        let xml = http
+            .get(url("/data?list-type=2&continuation-token=a.txt"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<Key>logs/b 1.log</Key>"));
+        assert!(!xml.contains("<Key>a.txt</Key>"));
+        // 取够一页后停止, 不会列出 logs/
+        let requests = server.requests();
+        let xml = http
+            .get(url("/data?list-type=2&max-keys=1"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<Key>a.txt</Key>"));
+        assert_eq!(server.requests() - requests, 3);
+        // 翻页时跳过 start-after 之前的 archive/, 只列出根目录和 logs/
+        let requests = server.requests();
+        let xml = http
+            .get(url("/data?list-type=2&continuation-token=logs%2Fb%201.log"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(xml.contains("<KeyCount>0</KeyCount><IsTruncated>false</IsTruncated>"));
+        assert_eq!(server.requests() - requests, 2);
+
+        let resp = http
+            .get(url("/data/a.txt"))
+            .header("Range", "bytes=6-7")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 206);
+        assert_eq!(resp.headers()["Content-Range"], "bytes 6-7/16");
+        assert_eq!(resp.text().await.unwrap(), "s3");
+        let resp = http
+            .get(url("/data/a.txt"))
+            .header("Range", "bytes=100-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 416);
+        let resp = http.head(url("/data/a.txt")).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["Content-Length"], "16");
+        let resp = http.get(url("/data/logs")).send().await.unwrap();
+        assert_eq!(resp.status(), 404);
+        assert!(resp
+            .text()
+            .await
+            .unwrap()
+            .contains("<Code>NoSuchKey</Code>"));
+        let resp = http.get(url("/missing/a.txt")).send().await.unwrap();
+        assert!(resp
+            .text()
+            .await
+            .unwrap()
+            .contains("<Code>NoSuchBucket</Code>"));
+        // 包含 .. 的 key 不能访问 bucket 之外的文件, reqwest 会整理 url 中的 .., 直接发送请求
+        async fn raw_request(addr: std::net::SocketAddr, head: &str) -> String {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!("{}\r\nHost: {}\r\nConnection: close\r\n\r\n", head, addr);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut resp = String::new();
+            stream.read_to_string(&mut resp).await.unwrap();
+            resp
+        }
+        for head in [
+            "GET /data/%2e%2e/other.txt HTTP/1.1",
+            "GET /data/logs/../../other.txt HTTP/1.1",
+            "GET /data?list-type=2&prefix=../ HTTP/1.1",
+            "PUT /data/stolen.txt HTTP/1.1\r\nx-amz-copy-source: /data/../other.txt\r\nContent-Length: 0",
+        ] {
+            let resp = raw_request(s3.local_addr(), head).await;
+            assert!(resp.starts_with("HTTP/1.1 400"), "{}", head);
+            assert!(resp.contains("<Code>InvalidArgument</Code>"));
+        }
+        assert!(!server.exists("/cloud/data/stolen.txt"));
+
+        let resp = http
+            .put(url("/data/new/c.txt"))
+            .body("uploaded")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            server.read_file("/cloud/data/new/c.txt").unwrap(),
+            b"uploaded"
+        );
+        let resp = http
+            .put(url("/data/chunked.txt"))
+            .header("x-amz-content-sha256", "STREAMING-UNSIGNED-PAYLOAD-TRAILER")
+            .body("5\r\nhello\r\n6\r\n world\r\n0\r\nx-amz-checksum-crc32:AAAAAA==\r\n\r\n")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            server.read_file("/cloud/data/chunked.txt").unwrap(),
+            b"hello world"
+        );
+
+        let resp = http
+            .put(url("/data/copies/a2.txt"))
+            .header("x-amz-copy-source", "/data/a.txt")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert!(resp.text().await.unwrap().contains("<CopyObjectResult"));
+        assert_eq!(
+            server.read_file("/cloud/data/copies/a2.txt").unwrap(),
+            b"hello s3 gateway"
+        );
+        assert!(!server.exists("/cloud/data/copies/a.txt"));
+
+        let resp = http
+            .delete(url("/data/copies/a2.txt"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 204);
+        assert!(!server.exists("/cloud/data/copies/a2.txt"));
+        // 目录只有在为空时才会被删除
+        let resp = http.delete(url("/data/logs/")).send().await.unwrap();
+        assert_eq!(resp.status(), 204);
+        assert!(server.exists("/cloud/data/logs/b 1.log"));
+        let resp = http.delete(url("/data/missing.txt")).send().await.unwrap();
+        assert_eq!(resp.status(), 204);
+    }
+
+    #[cfg(feature = "crypt")]
+    #[tokio::test]
+    async fn test_crypt_fs() {
+        use crypt::{Cipher, CryptFs, NameEncryption};
+        use std::io::ErrorKind;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use vfs::RemoteFs;
+
+        let server = MockServer::start().await;
+        server.add_dir("/cloud/private");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let cipher = Cipher::new("password", "").unwrap();
+        let fs = CryptFs::new(client.clone(), "/cloud/private", cipher);
+
+        // 跨越多个加密块
+        let content: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        fs.create_dir("/reports").await.unwrap();
+        let mut writer = fs.create_write("/reports/2024 Q1.csv").await.unwrap();
+        writer.write_all(&content).await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        let encrypted = fs.resolve("/reports/2024 Q1.csv").unwrap();
+        assert!(encrypted.starts_with("/cloud/private/"));
+        assert!(!encrypted.contains("reports") && !encrypted.contains("Q1"));
+        let stored = server.read_file(&encrypted).unwrap();
+        assert_eq!(
+            stored.len() as u64,
+            Cipher::encrypted_size(content.len() as u64)
+        );
+        assert!(!stored.windows(16).any(|w| w == &content[1000..1016]));
+
+        let entries = fs.read_dir("/reports").await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "2024 Q1.csv");
+        assert_eq!(entries[0].size, content.len() as u64);
+        let mut data = Vec::new();
+        let mut reader = fs.open_read("/reports/2024 Q1.csv").await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, content);
+
+        fs.rename("/reports/2024 Q1.csv", "/reports/q1.csv")
+            .await
+            .unwrap();
+        let meta = fs.metadata("/reports/q1.csv").await.unwrap();
+        assert_eq!((meta.name.as_str(), meta.size), ("q1.csv", 150_000));
+        // 不能通过 .. 访问 root 之外的文件
+        assert!(fs.resolve("/reports/../../plain.txt").is_err());
+        assert!(fs.create_write("/../x").await.is_err());
+        assert!(!server.exists("/cloud/x"));
+        // 未加密的文件不会列出
+        server.add_file("/cloud/private/plain.txt", b"plain");
+        assert_eq!(fs.read_dir("/").await.unwrap().len(), 1);
+
+        // 错误的密钥和被截断的文件都无法解密
+        let encrypted = fs.resolve("/reports/q1.csv").unwrap();
+        let other = CryptFs::new(
+            client,
+            "/",
+            Cipher::from_key(&[7; 32]).with_names(NameEncryption::Off),
+        );
+        let mut reader = other.open_read(&encrypted).await.unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let stored = server.read_file(&encrypted).unwrap();
+        server.add_file(&encrypted, &stored[..27 + 65536 + 16]);
+        let mut reader = fs.open_read("/reports/q1.csv").await.unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let cipher = Cipher::from_key(&[1; 32]).with_names(NameEncryption::Obfuscate);
+        let name = cipher.encrypt_name("Report-2024.txt");
+        assert_ne!(name, "Report-2024.txt");
+        assert_eq!(cipher.decrypt_name(&name).unwrap(), "Report-2024.txt");
+        assert_eq!(Cipher::decrypted_size(Cipher::encrypted_size(0)), Some(0));
+        assert_eq!(
+            Cipher::decrypted_size(Cipher::encrypted_size(65536)),
+            Some(65536)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        use chunker::ChunkedFs;
+        use fs::{FileParams, UploadParams};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use vfs::RemoteFs;
+
+        let server = MockServer::start().await;
+        server.add_dir("/cloud");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let local = std::env::temp_dir().join(format!("alist-chunked-{}", std::process::id()));
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&local, &content).unwrap();
+        let params = |name: &str| UploadParams {
+            local_file: local.to_string_lossy().into_owned(),
+            remote_path: "/cloud".to_string(),
+            remote_name: name.to_string(),
+        };
+
+        let manifest = client
+            .upload_chunked(params("big.bin"), 4096)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(manifest.size, 10_000);
+        let sizes: Vec<u64> = manifest.parts.iter().map(|p| p.size).collect();
+        assert_eq!(sizes, [4096, 4096, 1808]);
+        assert!(server.exists("/cloud/big.bin.chunked/00003.part"));
+        assert!(server.exists("/cloud/big.bin.chunked/manifest.json"));
+        assert!(!server.exists("/cloud/big.bin"));
+        assert!(!server.exists("/cloud/big.bin.chunked.uploading"));
+        // 上传失败时保留原先的版本
+        server.inject_for("/api/fs/put", Fault::ServerError);
+        assert!(client
+            .upload_chunked(params("big.bin"), 2048)
+            .await
+            .is_err());
+        assert!(!server.exists("/cloud/big.bin.chunked.uploading"));
+        assert_eq!(
+            ChunkedFs::new(client.clone(), 4096)
+                .manifest("/cloud/big.bin")
+                .await
+                .unwrap(),
+            manifest
+        );
+        // 替换时改名失败, 恢复原先的版本
+        server.inject_for("/api/fs/rename", Fault::Delay(Duration::ZERO));
+        server.inject_for("/api/fs/rename", Fault::ServerError);
+        assert!(client
+            .upload_chunked(params("big.bin"), 2048)
+            .await
+            .is_err());
+        assert_eq!(
+            ChunkedFs::new(client.clone(), 4096)
+                .manifest("/cloud/big.bin")
+                .await
+                .unwrap(),
+            manifest
+        );
+        assert!(!server.exists("/cloud/big.bin.chunked.uploading"));
+        assert!(!server.exists("/cloud/big.bin.chunked.backup"));
+        // 恢复也失败时保留暂存目录, 不会删除唯一完整的版本
+        server.inject_for("/api/fs/rename", Fault::Delay(Duration::ZERO));
+        server.inject_for("/api/fs/rename", Fault::ServerError);
+        server.inject_for("/api/fs/rename", Fault::ServerError);
+        assert!(client
+            .upload_chunked(params("big.bin"), 2048)
+            .await
+            .is_err());
+        assert!(server.exists("/cloud/big.bin.chunked.uploading/manifest.json"));
+        assert!(server.exists("/cloud/big.bin.chunked.backup/manifest.json"));
+        // 下次上传时清理上次留下的备份
+        let replaced = client
+            .upload_chunked(params("big.bin"), 4096)
+            .await
+            .unwrap();
+        assert_eq!(replaced, Some(manifest.clone()));
+        assert!(!server.exists("/cloud/big.bin.chunked.backup"));
+        // 不超过 chunk_size 时保存为普通文件
+        let small = client
+            .upload_chunked(params("small.bin"), 1 << 20)
+            .await
+            .unwrap();
+        assert!(small.is_none());
+        assert_eq!(server.read_file("/cloud/small.bin").unwrap(), content);
+        assert!(!server.exists("/cloud/small.bin.chunked"));
+
+        let fs = ChunkedFs::new(client.clone(), 4096);
+        // 通过 create_write 写入时分块与 upload_chunked 相同
+        let mut writer = fs.create_write("/cloud/stream.bin").await.unwrap();
+        writer.write_all(&content).await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(fs.manifest("/cloud/stream.bin").await.unwrap(), manifest);
+        fs.remove("/cloud/stream.bin").await.unwrap();
+
+        let entries = fs.read_dir("/cloud").await.unwrap();
+        let big = entries.iter().find(|m| m.name == "big.bin").unwrap();
+        assert_eq!((big.is_dir, big.size), (false, 10_000));
+        assert_eq!(fs.metadata("/cloud/big.bin").await.unwrap().size, 10_000);
+        let downloaded = local.with_extension("download");
+        let downloaded_str = downloaded.to_str().unwrap();
+        let written = client
+            .download_chunked(
+                FileParams {
+                    path: Some("/cloud/big.bin".to_string()),
+                    ..Default::default()
+                },
+                downloaded_str,
+            )
+            .await
+            .unwrap();
+        assert_eq!(written, 10_000);
+        assert_eq!(std::fs::read(&downloaded).unwrap(), content);
+
+        fs.rename("/cloud/big.bin", "/cloud/master.bin")
+            .await
+            .unwrap();
+        assert!(server.exists("/cloud/master.bin.chunked/00001.part"));
+        let mut data = Vec::new();
+        let mut reader = fs.open_read("/cloud/master.bin").await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, content);
+        // 分块列表与 manifest 中原文件的大小和 sha256 不符时读取失败
+        let mut tampered = fs.manifest("/cloud/master.bin").await.unwrap();
+        tampered.parts.pop();
+        server.add_file(
+            "/cloud/master.bin.chunked/manifest.json",
+            &serde_json::to_vec(&tampered).unwrap(),
+        );
+        let mut reader = fs.open_read("/cloud/master.bin").await.unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        server.add_file(
+            "/cloud/master.bin.chunked/manifest.json",
+            &serde_json::to_vec(&manifest).unwrap(),
+        );
+        // 分块内容被修改时读取失败
+        server.add_file("/cloud/master.bin.chunked/00002.part", &[0; 4096]);
+        let mut reader = fs.open_read("/cloud/master.bin").await.unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        fs.remove("/cloud/master.bin").await.unwrap();
+        assert!(!server.exists("/cloud/master.bin.chunked"));
+
+        let _ = std::fs::remove_file(&local);
+        let _ = std::fs::remove_file(&downloaded);
+    }
+
+    #[tokio::test]
+    async fn test_connect_token_cache() {
+        let server = MockServer::start().await;
+        let profile = profile::Profile {
+            server: server.url(),
+            username: Some(MockServer::USERNAME.to_string()),
+            password: Some(profile::Secret::Command(format!(
+                "echo {}",
+                MockServer::PASSWORD
+            ))),
+            token: None,
+            connection: Default::default(),
+        };
+        let mut cache = profile::TokenCache::default();
+        let (client, changed) = AlistClient::connect_with_cache("home", &profile, &mut cache)
+            .await
+            .unwrap();
+        assert!(changed);
+        let token = client.token().to_string();
+        assert_eq!(
+            cache.get("home", &server.url(), MockServer::USERNAME),
+            Some(token.as_str())
+        );
+
+        let (client, changed) = AlistClient::connect_with_cache("home", &profile, &mut cache)
+            .await
+            .unwrap();
+        assert!(!changed);
+        assert_eq!(client.token(), token);
+
+        // 服务端不再接受缓存的 token 时重新登录
+        let stale = profile::CachedToken {
+            server: server.url(),
+            username: MockServer::USERNAME.to_string(),
+            token: "stale".to_string(),
+            expires_at: profile::unix_now() + 3600,
+        };
+        cache.insert("home", stale);
+        let (client, changed) = AlistClient::connect_with_cache("home", &profile, &mut cache)
+            .await
+            .unwrap();
+        assert!(changed);
+        assert_ne!(client.token(), "stale");
+        assert_eq!(
+            cache.get("home", &server.url(), MockServer::USERNAME),
+            Some(client.token())
+        );
+
+        // 已有的缓存文件权限过宽时改为只有当前用户可以读写
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = std::env::temp_dir().join(format!("alist-tokens-{}", std::process::id()));
+            std::fs::write(&path, "{}").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            cache.save_to(&path).unwrap();
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_settings_schema() {
+        let mut data: serde_json::Value = serde_json::from_str(
+            r#"{"allow_indexed":"false","allow_mounted":"true","announcement":"","audio_autoplay":"true","audio_cover":"","auto_update_index":"false","default_page_size":"30","external_previews":"","favicon":"","hide_files":"","home_container":"max_width","home_icon":"","iframe_previews":"{}","logo":"","main_color":"","ocr_api":"","package_download":"true","pagination_type":"all","robots_txt":"","search_index":"none","settings_layout":"list","site_title":"AList","sso_login_enabled":"false","video_autoplay":"true","version":"v3.11.0"}"#,
+        )
+        .unwrap();
+        // 旧版本没有的设置项为空字符串
+        let settings: public::Settings = serde_json::from_value(data.clone()).unwrap();
+        assert_eq!(settings.archive_extensions, "");
+        assert!(!settings.is_archive("a.zip"));
+        // 所有版本都有的设置项缺失时解析失败
+        data.as_object_mut().unwrap().remove("site_title");
+        assert!(serde_json::from_value::<public::Settings>(data).is_err());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        use capability::{Capabilities, Capability, ServerVersion};
+
+        let version = ServerVersion::parse("v3.42.0-beta").unwrap();
+        assert_eq!(version, ServerVersion::new(3, 42, 0));
+        assert_eq!(ServerVersion::parse("3.9").unwrap().to_string(), "v3.9.0");
+        assert!(ServerVersion::parse("dev").is_err());
+
+        let caps = Capabilities::for_version(&ServerVersion::new(3, 11, 0));
+        assert!(caps.contains(Capability::LoginHash));
+        assert!(caps.contains(Capability::LegacyOfflineDownload));
+        assert!(!caps.contains(Capability::OfflineDownload));
+        assert!(!caps.contains(Capability::Archive));
+        let caps = Capabilities::for_version(&version);
+        assert!(caps.contains(Capability::Archive));
+        assert!(!caps.contains(Capability::LegacyOfflineDownload));
+        assert_eq!(AlistClient::new(SERVER).capabilities(), Capabilities::all());
+    }
+
+    #[tokio::test]
+    async fn test_version_gating() {
+        let server = MockServer::start().await;
+        server.set_setting("version", "v3.7.2");
+        let mut client = AlistClient::new(&server.url());
+        let version = client.detect_version().await.unwrap();
+        assert_eq!(version.unwrap().to_string(), "v3.7.2");
+        // 旧版本使用明文密码登录
+        client
+            .login(MockServer::USERNAME, MockServer::PASSWORD)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        let err = client
+            .archive_meta(archive::ArchiveMetaParams {
+                path: "/cloud/a.zip".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "archive is unsupported by server v3.7.2");
+        let err = client
+            .add_offline_download(fs::OfflineDownloadParams {
+                task: fs::OfflineTaskParams {
+                    path: "/cloud".to_string(),
+                    urls: vec!["https://example.com/a.iso".to_string()],
+                },
+                tool: "SimpleHttp".to_string(),
+                delete_policy: fs::DeletePolicy::default(),
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Unsupported { .. }));
+        assert_eq!(server.requests(), requests);
+
+        server.set_setting("version", "dev");
+        assert_eq!(client.detect_version().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_plan_regex_rename() {
+        let names: Vec<String> = ["S01E01.mkv", "S01E02.mkv", "E02.mkv", "notes.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rules = vec![fs::RegexRenameParams {
+            src_name_regex: r"^S01(E\d+)\.mkv$".to_string(),
+            new_name_regex: "$1.mkv".to_string(),
+        }];
+        let plan = fs::plan_regex_rename("/cloud/show", &names, &rules).unwrap();
+        assert_eq!(plan.entries.len(), 2);
+        assert_eq!(plan.entries[0].new_name, "E01.mkv");
+        assert_eq!(plan.entries[0].status, fs::RenameStatus::Rename);
+        assert_eq!(plan.entries[1].status, fs::RenameStatus::Collision);
+        assert!(plan.has_collisions());
+
+        let rules = vec![fs::RegexRenameParams {
+            src_name_regex: r"\.txt$".to_string(),
+            new_name_regex: ".txt".to_string(),
+        }];
+        let plan = fs::plan_regex_rename("/cloud/show", &names, &rules).unwrap();
+        assert_eq!(plan.entries[0].status, fs::RenameStatus::NoOp);
+        assert_eq!(plan.renames().count(), 0);
+    }
+
+    #[test]
+    fn test_archive_inner_download_url() {
+        let url = archive::inner_download_url(
+            SERVER,
+            "/cloud/测试 1.zip",
+            "/docs/a b.txt",
+            Some("secret"),
+            "abc:0",
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "http://127.0.0.1:5244/ad/cloud/%E6%B5%8B%E8%AF%95%201.zip?inner=%2Fdocs%2Fa+b.txt&pass=secret&sign=abc%3A0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline_download_legacy_fallback() {
+        let server = MockServer::start().await;
+        let token = login(&server).await;
+        let params = || fs::OfflineTaskParams {
+            path: "/cloud".to_string(),
+            urls: vec!["https://example.com/a.iso".to_string()],
+        };
+        fs::add_aria2_task(&server.url(), &token, params())
+            .await
+            .unwrap();
+        fs::add_qbit_task(&server.url(), &token, params())
+            .await
+            .unwrap();
+        assert_eq!(server.offline_tasks(), 2);
+
+        // 其它工具没有旧接口, 返回服务端的错误, 即无法解析的前端页面
+        let err = fs::add_offline_download(
+            &server.url(),
+            &token,
+            fs::OfflineDownloadParams {
+                task: params(),
+                tool: "SimpleHttp".to_string(),
+                delete_policy: fs::DeletePolicy::default(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::Decode(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_index() {
+        let server = MockServer::start().await;
+        server.add_file("/cloud/a.txt", b"a");
+        server.add_file("/cloud/docs/b.txt", b"b");
+        let token = login(&server).await;
+        let client = AlistClient::new(&server.url()).with_token(&token);
+        let params = |path: &str| admin::IndexParams {
+            paths: vec![path.to_string()],
+            max_depth: -1,
+        };
+
+        // 第一次轮询仍是上一次构建的错误, 不能当作本次的结果
+        server.set_index_progress(serde_json::json!({
+            "obj_count": 0,
+            "is_done": true,
+            "last_done_time": "2024-01-02T11:04:05+08:00",
+            "error": "failed to walk /old: object not found",
+        }));
+        let mut polls = Vec::new();
+        let progress = client
+            .rebuild_index(params("/cloud"), Duration::from_millis(1), |p| {
+                polls.push(p.clone())
+            })
+            .await
+            .unwrap();
+        assert_eq!(polls.len(), 2);
+        assert!(!polls[0].error.is_empty());
+        assert_eq!((progress.obj_count, progress.is_done), (3, true));
+        assert_eq!(progress.error, "");
+        assert_eq!(polls[1], progress);
+
+        // 本次构建出错
+        let err = client
+            .rebuild_index(params("/missing"), Duration::from_millis(1), |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "failed to walk /missing: object not found");
+
+        admin::update_index(&server.url(), &token, params("/cloud/docs"))
+            .await
+            .unwrap();
+        admin::stop_index(&server.url(), &token).await.unwrap();
+        admin::clear_index(&server.url(), &token).await.unwrap();
+        let progress = admin::index_progress(&server.url(), &token).await.unwrap();
+        assert_eq!((progress.obj_count, progress.is_done), (0, true));
+    }
+
+    #[test]
+    fn test_offline_download_params() {
+        let params = fs::OfflineDownloadParams {
+            task: fs::OfflineTaskParams {
+                path: "/cloud/downloads".to_string(),
+                urls: vec!["magnet:?xt=urn:btih:0".to_string()],
+            },
+            tool: "qBittorrent".to_string(),
+            delete_policy: fs::DeletePolicy::DeleteNever,
+        };
+        assert_eq!(
+            serde_json::to_value(&params).unwrap(),
+            serde_json::json!({
+                "path": "/cloud/downloads",
+                "urls": ["magnet:?xt=urn:btih:0"],
+                "tool": "qBittorrent",
+                "delete_policy": "delete_never",
+            })
+        );
+    }
+
+    #[test]
+    fn test_signer() {
+        let signer = sign::Signer::new(SERVER, "alist-token");
+        assert_eq!(
+            signer.sign("/cloud/a.txt", 0),
+            "ulvxfAovy-chBY1TCitHQlTYHJtkkKORVnAiduSG8j0=:0"
+        );
+        assert_eq!(
+            signer.sign_url("/cloud/a.txt", None).unwrap(),
+            "http://127.0.0.1:5244/d/cloud/a.txt?sign=ulvxfAovy-chBY1TCitHQlTYHJtkkKORVnAiduSG8j0%3D%3A0"
+        );
+        assert!(signer
+            .verify("/cloud/a.txt", &signer.sign("/cloud/a.txt", 0))
+            .is_ok());
+        assert!(signer
+            .verify("/cloud/b.txt", &signer.sign("/cloud/a.txt", 0))
+            .is_err());
+        assert_eq!(
+            signer
+                .verify("/cloud/a.txt", &signer.sign("/cloud/a.txt", 1700000000))
+                .unwrap_err()
+                .to_string(),
+            "sign expired"
+        );
+    }
+
+    #[test]
+    fn test_video_preview_schema() {
+        let resp: Response<fs::VideoPreview> = serde_json::from_value(serde_json::json!({
+            "code": 200,
+            "message": "success",
+            "data": {
+                "category": "live_transcoding",
+                "meta": {"duration": 1423.5, "width": 1920, "height": 1080},
+                "live_transcoding_task_list": [
+                    {"template_id": "FHD", "template_width": 1920, "template_height": 1080, "status": "finished", "url": "https://example.com/fhd.m3u8"},
+                    {"template_id": "HD", "status": "running"}
+                ],
+                "live_transcoding_subtitle_task_list": [
+                    {"language": "chi", "status": "finished", "url": "https://example.com/chi.vtt"}
+                ]
+            }
+        }))
+        .unwrap();
+        let preview = resp.data.unwrap();
+        assert_eq!(preview.qualities.len(), 2);
+        assert_eq!(preview.qualities[1].url, "");
+        assert_eq!(preview.subtitles[0].language, "chi");
+    }
+
+    #[test]
+    fn test_profile_config() {
+        let config = profile::Config::parse(
+            r#"
+            default = "home"
+
+            [profiles.home]
+            server = "http://127.0.0.1:5244"
+            username = "admin"
+            password = { value = "123456" }
+
+            [profiles.nas]
+            server = "https://nas.example.com"
+            token = { env = "ALIST_TEST_UNSET_TOKEN" }
+            "#,
+        )
+        .unwrap();
+        let (name, home) = config.profile(None).unwrap();
+        assert_eq!(name, "home");
+        assert_eq!(home.password.as_ref().unwrap().resolve().unwrap(), "123456");
+        let (_, nas) = config.profile(Some("nas")).unwrap();
+        assert!(nas.token.as_ref().unwrap().resolve().is_err());
+        assert!(config.profile(Some("missing")).is_err());
+
+        let token = "eyJhbGciOiJIUzI1NiJ9.eyJleHAiOjE3MDAwMDAwMDB9.c2ln";
+        assert_eq!(profile::token_expires_at(token), Some(1700000000));
     }
 }
@@ -0,0 +1,287 @@
+//! 客户端指标, 可导出为 Prometheus 文本格式
+//!
+//! 启用 metrics feature 后, 记录到 Metrics 的数据会同时写入 `metrics` crate 的全局 recorder,
+//! 指标名称相同, 可以使用已有的 exporter.
+//!
+//! ```
+//! use alistapi::{metrics::Metrics, AlistClient};
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(Metrics::new());
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_metrics(metrics.clone());
+//! // 在 /metrics 接口中返回
+//! let text = metrics.render_prometheus();
+//! ```
+use super::{Error, Response};
+use serde::de::IgnoredAny;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// 请求耗时的直方图分桶(秒), 与 Prometheus 客户端的默认值相同
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    // 与 BUCKETS 对应的累计计数
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if value <= le {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    // (endpoint, code) -> 请求数
+    requests: BTreeMap<(String, String), u64>,
+    durations: BTreeMap<String, Histogram>,
+    retries: BTreeMap<String, u64>,
+    uploaded_bytes: u64,
+    downloaded_bytes: u64,
+    token_refreshes: u64,
+}
+
+/// 请求数、耗时、上传下载字节数、重试次数和登录次数
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+/// 某个接口的请求统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointStats {
+    pub requests: u64,
+    pub retries: u64,
+    // 总耗时(秒)
+    pub duration_sum: f64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// 记录一次请求, code 为接口返回的 code, 无法解析时为 HTTP 状态码或错误类型
+    pub(crate) fn request(&self, endpoint: &str, code: &str, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        {
+            let mut registry = self.registry.lock().unwrap();
+            *registry
+                .requests
+                .entry((endpoint.to_string(), code.to_string()))
+                .or_default() += 1;
+            registry
+                .durations
+                .entry(endpoint.to_string())
+                .or_default()
+                .observe(seconds);
+        }
+        #[cfg(feature = "metrics")]
+        {
+            ::metrics::counter!(
+                "alist_requests_total",
+                "endpoint" => endpoint.to_string(),
+                "code" => code.to_string()
+            )
+            .increment(1);
+            ::metrics::histogram!(
+                "alist_request_duration_seconds",
+                "endpoint" => endpoint.to_string()
+            )
+            .record(seconds);
+        }
+    }
+
+    pub(crate) fn retry(&self, endpoint: &str) {
+        *self
+            .registry
+            .lock()
+            .unwrap()
+            .retries
+            .entry(endpoint.to_string())
+            .or_default() += 1;
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("alist_retries_total", "endpoint" => endpoint.to_string()).increment(1);
+    }
+
+    pub(crate) fn uploaded(&self, bytes: u64) {
+        self.registry.lock().unwrap().uploaded_bytes += bytes;
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("alist_uploaded_bytes_total").increment(bytes);
+    }
+
+    pub(crate) fn downloaded(&self, bytes: u64) {
+        self.registry.lock().unwrap().downloaded_bytes += bytes;
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("alist_downloaded_bytes_total").increment(bytes);
+    }
+
+    pub(crate) fn token_refresh(&self) {
+        self.registry.lock().unwrap().token_refreshes += 1;
+        #[cfg(feature = "metrics")]
+        ::metrics::counter!("alist_token_refreshes_total").increment(1);
+    }
+
+    pub fn endpoint(&self, endpoint: &str) -> EndpointStats {
+        let registry = self.registry.lock().unwrap();
+        EndpointStats {
+            requests: registry
+                .requests
+                .iter()
+                .filter(|((e, _), _)| e == endpoint)
+                .map(|(_, n)| n)
+                .sum(),
+            retries: registry.retries.get(endpoint).copied().unwrap_or_default(),
+            duration_sum: registry.durations.get(endpoint).map_or(0.0, |h| h.sum),
+        }
+    }
+
+    pub fn uploaded_bytes(&self) -> u64 {
+        self.registry.lock().unwrap().uploaded_bytes
+    }
+
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.registry.lock().unwrap().downloaded_bytes
+    }
+
+    pub fn token_refreshes(&self) -> u64 {
+        self.registry.lock().unwrap().token_refreshes
+    }
+
+    /// 导出为 Prometheus 文本格式
+    pub fn render_prometheus(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "alist_requests_total",
+            "counter",
+            "Requests by endpoint and API code.",
+        );
+        for ((endpoint, code), n) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "alist_requests_total{{endpoint=\"{}\",code=\"{}\"}} {}",
+                escape(endpoint),
+                escape(code),
+                n
+            );
+        }
+
+        header(
+            &mut out,
+            "alist_request_duration_seconds",
+            "histogram",
+            "Request latency in seconds.",
+        );
+        for (endpoint, histogram) in &registry.durations {
+            let endpoint = escape(endpoint);
+            for (le, n) in BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "alist_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, le, n
+                );
+            }
+            let _ = writeln!(
+                out,
+                "alist_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+                endpoint, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "alist_request_duration_seconds_sum{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "alist_request_duration_seconds_count{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.count
+            );
+        }
+
+        header(
+            &mut out,
+            "alist_retries_total",
+            "counter",
+            "Retried requests by endpoint.",
+        );
+        for (endpoint, n) in &registry.retries {
+            let _ = writeln!(
+                out,
+                "alist_retries_total{{endpoint=\"{}\"}} {}",
+                escape(endpoint),
+                n
+            );
+        }
+
+        for (name, help, value) in [
+            (
+                "alist_uploaded_bytes_total",
+                "Bytes uploaded.",
+                registry.uploaded_bytes,
+            ),
+            (
+                "alist_downloaded_bytes_total",
+                "Bytes downloaded.",
+                registry.downloaded_bytes,
+            ),
+            (
+                "alist_token_refreshes_total",
+                "Logins performed to obtain a token.",
+                registry.token_refreshes,
+            ),
+        ] {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+/// 请求结果对应的 code 标签
+pub(crate) fn code_label(result: &Result<(u16, Vec<u8>), Error>) -> String {
+    match result {
+        Ok((status, body)) => serde_json::from_slice::<Response<IgnoredAny>>(body)
+            .map_or_else(|_| status.to_string(), |resp| resp.code.to_string()),
+        Err(err) => error_label(err),
+    }
+}
+
+/// 请求失败时的 code 标签
+pub(crate) fn error_label(err: &Error) -> String {
+    match err {
+        Error::Api { code, .. } => code.to_string(),
+        Error::Status(status) => status.to_string(),
+        Error::Timeout => "timeout".to_string(),
+        Error::Cancelled => "cancelled".to_string(),
+        _ => "error".to_string(),
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
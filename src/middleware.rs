@@ -0,0 +1,181 @@
+//! 请求中间件, 用于添加请求头、审计日志、签名或注入故障
+//!
+//! 中间件按添加顺序包裹每次发往接口的请求, 重试时每次尝试都会经过中间件.
+//! 文件下载、压缩包内文件和 Range 读取直接返回文件内容, 不经过 handle,
+//! 发送前按顺序调用 prepare_raw, 只能修改请求或返回错误.
+//!
+//! ```
+//! use alistapi::middleware::{ApiRequest, ApiResponse, BoxFuture, Middleware, Next};
+//! use alistapi::{AlistClient, Error};
+//! use std::sync::Arc;
+//!
+//! struct Audit;
+//!
+//! impl Middleware for Audit {
+//!     fn handle<'a>(
+//!         &'a self,
+//!         req: ApiRequest,
+//!         next: Next<'a>,
+//!     ) -> BoxFuture<'a, Result<ApiResponse, Error>> {
+//!         Box::pin(async move {
+//!             let endpoint = req.endpoint.clone();
+//!             let resp = next.run(req).await?;
+//!             println!("{} -> {:?}", endpoint, resp.code());
+//!             Ok(resp)
+//!         })
+//!     }
+//! }
+//!
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_middleware(Arc::new(Audit));
+//! ```
+use super::client::AlistClient;
+pub use super::client::ApiRequest;
+use super::trace::RequestSpan;
+use super::{Error, Response};
+use reqwest::Method;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// 接口返回的 HTTP 状态码和响应内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl ApiResponse {
+    /// 解析为 Response, 非 2xx 且无法解析时返回 Error::Status
+    pub fn decode<Data: DeserializeOwned>(&self) -> Result<Response<Data>, Error> {
+        match serde_json::from_slice(&self.body) {
+            Ok(resp) => Ok(resp),
+            Err(_) if !(200..300).contains(&self.status) => Err(Error::Status(self.status)),
+            Err(e) => Err(Error::Decode(e)),
+        }
+    }
+
+    /// 接口返回的 code, 响应不是 json 时为 None
+    pub fn code(&self) -> Option<isize> {
+        serde_json::from_slice::<Response<IgnoredAny>>(&self.body)
+            .ok()
+            .map(|resp| resp.code)
+    }
+}
+
+/// 直接返回文件内容的请求, 如文件下载和 Range 读取
+#[derive(Debug, Clone)]
+pub struct RawRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    // 请求发往 alist 服务端, 为 false 时可能是第三方存储的直链
+    pub to_server: bool,
+}
+
+impl RawRequest {
+    pub fn header(&mut self, key: &str, value: &str) {
+        self.headers.push((key.to_string(), value.to_string()));
+    }
+}
+
+/// 包裹请求的中间件, 调用 next.run 继续发送, 也可以直接返回响应
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(
+        &'a self,
+        req: ApiRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<ApiResponse, Error>>;
+
+    /// 发送文件下载等请求前调用, 返回错误时不发送请求, 默认不做修改
+    fn prepare_raw(&self, _req: &mut RawRequest) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// 为每个请求添加固定的请求头, 如前置代理需要的认证信息
+#[derive(Debug, Clone, Default)]
+pub struct SetHeaders {
+    headers: Vec<(String, String)>,
+}
+
+impl SetHeaders {
+    pub fn new() -> Self {
+        SetHeaders::default()
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Middleware for SetHeaders {
+    fn handle<'a>(
+        &'a self,
+        mut req: ApiRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<ApiResponse, Error>> {
+        for (key, value) in &self.headers {
+            req = req.header(key, value);
+        }
+        Box::pin(next.run(req))
+    }
+
+    /// 不向第三方存储发送这些请求头
+    fn prepare_raw(&self, req: &mut RawRequest) -> Result<(), Error> {
+        if req.to_server {
+            for (key, value) in &self.headers {
+                req.header(key, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 中间件链中剩余的部分, 最后由客户端发送请求
+pub struct Next<'a> {
+    client: &'a AlistClient,
+    span: &'a RequestSpan,
+    chain: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        client: &'a AlistClient,
+        span: &'a RequestSpan,
+        chain: &'a [Arc<dyn Middleware>],
+    ) -> Self {
+        Next {
+            client,
+            span,
+            chain,
+        }
+    }
+
+    pub async fn run(self, req: ApiRequest) -> Result<ApiResponse, Error> {
+        match self.chain.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next::new(self.client, self.span, rest);
+                middleware.handle(req, next).await
+            }
+            None => {
+                let (status, body) = self.client.send_once(&req, self.span).await?;
+                Ok(ApiResponse { status, body })
+            }
+        }
+    }
+}
+
+/// 客户端上的中间件列表
+#[derive(Clone, Default)]
+pub(crate) struct Chain(pub Vec<Arc<dyn Middleware>>);
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Chain({} middleware)", self.0.len())
+    }
+}
@@ -0,0 +1,770 @@
+//! 进程内的 alist 模拟服务端, 用于离线测试
+//!
+//! ```no_run
+//! # async fn example() {
+//! use alistapi::{auth, fs, mock::MockServer};
+//!
+//! let server = MockServer::start().await;
+//! server.add_file("/cloud/a.txt", b"hello");
+//! let token = auth::login(&server.url(), MockServer::USERNAME, MockServer::PASSWORD)
+//!     .await
+//!     .unwrap();
+//! let data = fs::listdir(&server.url(), &token, fs::FileParams {
+//!     path: Some("/cloud".to_string()),
+//!     ..Default::default()
+//! })
+//! .await
+//! .unwrap();
+//! assert_eq!(data.total, 1);
+//! # }
+//! ```
+use super::auth::sha256;
+use super::time::format_rfc3339;
+use super::vfs::split_path;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// 注入到后续请求中的故障
+#[derive(Debug, Clone)]
+pub enum Fault {
+    // 返回 code 401, 与 token 失效时一致
+    Unauthorized,
+    // 延迟后再正常响应
+    Delay(Duration),
+    // 返回无法解析的 json
+    MalformedJson,
+    // 返回 HTTP 500
+    ServerError,
+    // 返回 HTTP 200 和 code 401 的 json, 与下载链接的签名过期时一致
+    LinkExpired,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    is_dir: bool,
+    content: Vec<u8>,
+    modified: u64,
+}
+
+#[derive(Default)]
+struct State {
+    nodes: BTreeMap<String, Node>,
+    tokens: HashSet<String>,
+    // (路径前缀, 故障), 按顺序匹配, 每个故障只生效一次
+    faults: VecDeque<(String, Fault)>,
+    settings: BTreeMap<String, String>,
+    requests: usize,
+    // 已添加的离线下载链接数
+    offline_tasks: usize,
+    // 索引进度, 与 /api/admin/index/progress 返回的 data 相同
+    index: Value,
+    // 索引在服务端异步构建, (剩余的轮询次数, 构建完成后的进度)
+    index_pending: Option<(usize, Value)>,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    pub const USERNAME: &'static str = "admin";
+    pub const PASSWORD: &'static str = "123456";
+    pub const VERSION: &'static str = "v3.42.0";
+
+    /// 在随机端口启动, 只包含根目录
+    pub async fn start() -> MockServer {
+        let mut state = State::default();
+        state.nodes.insert(
+            "/".to_string(),
+            Node {
+                is_dir: true,
+                content: Vec::new(),
+                modified: now(),
+            },
+        );
+        state.settings = default_settings();
+        state.index = json!({
+            "obj_count": 0,
+            "is_done": false,
+            "last_done_time": null,
+            "error": "",
+        });
+        let state = Arc::new(Mutex::new(state));
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = rx.await;
+        }));
+        MockServer {
+            addr,
+            state,
+            shutdown: Some(tx),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 新建目录, 会同时创建不存在的上级目录
+    pub fn add_dir(&self, path: &str) {
+        let mut state = self.state.lock().unwrap();
+        create_dirs(&mut state, &normalize(path));
+    }
+
+    /// 新建文件, 会同时创建不存在的上级目录
+    pub fn add_file(&self, path: &str, content: &[u8]) {
+        let path = normalize(path);
+        let mut state = self.state.lock().unwrap();
+        create_dirs(&mut state, &split_path(&path).0);
+        state.nodes.insert(
+            path,
+            Node {
+                is_dir: false,
+                content: content.to_vec(),
+                modified: now(),
+            },
+        );
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .nodes
+            .contains_key(&normalize(path))
+    }
+
+    pub fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .nodes
+            .get(&normalize(path))
+            .filter(|n| !n.is_dir)
+            .map(|n| n.content.clone())
+    }
+
+    /// 修改 /api/public/settings 返回的设置项
+    pub fn set_setting(&self, key: &str, value: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.settings.insert(key.to_string(), value.to_string());
+    }
+
+    /// 修改索引进度, 如模拟上一次构建留下的错误
+    pub fn set_index_progress(&self, progress: Value) {
+        self.state.lock().unwrap().index = progress;
+    }
+
+    /// 对下一个请求注入故障
+    pub fn inject(&self, fault: Fault) {
+        self.inject_for("/", fault);
+    }
+
+    /// 对下一个路径以 prefix 开头的请求注入故障
+    pub fn inject_for(&self, prefix: &str, fault: Fault) {
+        let mut state = self.state.lock().unwrap();
+        state.faults.push_back((prefix.to_string(), fault));
+    }
+
+    /// 已收到的请求数
+    pub fn requests(&self) -> usize {
+        self.state.lock().unwrap().requests
+    }
+
+    /// 已添加的离线下载链接数
+    pub fn offline_tasks(&self) -> usize {
+        self.state.lock().unwrap().offline_tasks
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 节点的修改时间, 精确到秒
+fn modified(node: &Node) -> String {
+    format_rfc3339(UNIX_EPOCH + Duration::from_secs(node.modified), 0)
+}
+
+/// 构建索引完成后的进度, 路径不存在时与服务端一样记录错误
+fn build_index(state: &State, body: &Value) -> Value {
+    // 服务端的时间精确到纳秒, 连续构建的完成时间不会相同
+    let done_time = format_rfc3339(SystemTime::now(), 9);
+    let mut obj_count = 0;
+    let mut error = String::new();
+    for path in names_field(body, "paths") {
+        let path = normalize(&path);
+        if !state.nodes.contains_key(&path) {
+            error = format!("failed to walk {}: object not found", path);
+            break;
+        }
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        obj_count += state
+            .nodes
+            .keys()
+            .filter(|key| key.starts_with(&prefix))
+            .count();
+    }
+    json!({
+        "obj_count": obj_count,
+        "is_done": true,
+        "last_done_time": done_time,
+        "error": error,
+    })
+}
+
+/// 与 alist 一样整理路径中的 . 和 ..
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    normalize(&format!("{}/{}", dir, name))
+}
+
+fn name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+fn create_dirs(state: &mut State, path: &str) {
+    let mut current = String::new();
+    for part in path.split('/').filter(|p| !p.is_empty()) {
+        current = format!("{}/{}", current, part);
+        state.nodes.entry(current.clone()).or_insert(Node {
+            is_dir: true,
+            content: Vec::new(),
+            modified: now(),
+        });
+    }
+}
+
+fn children<'a>(state: &'a State, dir: &str) -> Vec<(&'a String, &'a Node)> {
+    state
+        .nodes
+        .iter()
+        .filter(|(path, _)| path.as_str() != "/" && split_path(path).0 == dir)
+        .collect()
+}
+
+/// 移动或复制 src 及其子项到 dst
+fn transfer(state: &mut State, src: &str, dst: &str, remove: bool) {
+    let prefix = format!("{}/", src);
+    let moved: Vec<(String, Node)> = state
+        .nodes
+        .iter()
+        .filter(|(path, _)| path.as_str() == src || path.starts_with(&prefix))
+        .map(|(path, node)| (format!("{}{}", dst, &path[src.len()..]), node.clone()))
+        .collect();
+    if remove {
+        state
+            .nodes
+            .retain(|path, _| path != src && !path.starts_with(&prefix));
+    }
+    state.nodes.extend(moved);
+}
+
+fn default_settings() -> BTreeMap<String, String> {
+    let mut settings = BTreeMap::new();
+    for key in [
+        "allow_indexed",
+        "allow_mounted",
+        "announcement",
+        "audio_autoplay",
+        "audio_cover",
+        "auto_update_index",
+        "default_page_size",
+        "external_previews",
+        "favicon",
+        "filename_char_mapping",
+        "forward_direct_link_params",
+        "hide_files",
+        "home_container",
+        "home_icon",
+        "iframe_previews",
+        "logo",
+        "main_color",
+        "ocr_api",
+        "package_download",
+        "pagination_type",
+        "robots_txt",
+        "search_index",
+        "settings_layout",
+        "site_title",
+        "sso_login_enabled",
+        "sso_login_platform",
+        "video_autoplay",
+    ] {
+        settings.insert(key.to_string(), String::new());
+    }
+    settings.insert("site_title".to_string(), "AList".to_string());
+    settings.insert("search_index".to_string(), "database".to_string());
+    settings.insert("archive_extensions".to_string(), "zip,7z,rar".to_string());
+    settings.insert("version".to_string(), MockServer::VERSION.to_string());
+    settings
+}
+
+fn reply(data: Value) -> Response<Body> {
+    reply_code(200, "success", data)
+}
+
+/// 与服务端一样, 未知路径返回前端页面
+fn reply_index() -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "text/html")
+        .body(Body::from("<!DOCTYPE html><html><body>alist</body></html>"))
+        .unwrap()
+}
+
+fn reply_code(code: isize, message: &str, data: Value) -> Response<Body> {
+    let body = json!({"code": code, "message": message, "data": data});
+    Response::builder()
+        .header("Content-Type", "application/json; charset=utf-8")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// 解析 bytes=start-end, end 可以省略
+fn parse_range(value: &str) -> Option<(usize, Option<usize>)> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let end = match end {
+        "" => None,
+        end => Some(end.parse().ok()?),
+    };
+    Some((start.parse().ok()?, end))
+}
+
+fn reply_range(content: &[u8], start: usize, end: Option<usize>) -> Response<Body> {
+    let end = end.map_or(content.len(), |end| (end + 1).min(content.len()));
+    if start >= end {
+        return Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header("Content-Range", format!("bytes */{}", content.len()))
+            .body(Body::empty())
+            .unwrap();
+    }
+    Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(
+            "Content-Range",
+            format!("bytes {}-{}/{}", start, end - 1, content.len()),
+        )
+        .body(Body::from(content[start..end].to_vec()))
+        .unwrap()
+}
+
+fn dir_entry(path: &str, node: &Node) -> Value {
+    json!({
+        "name": name(path),
+        "size": node.content.len(),
+        "is_dir": node.is_dir,
+        "modified": modified(node),
+        "created": modified(node),
+        "sign": "",
+        "thumb": "",
+        "type": if node.is_dir { 1 } else { 0 },
+        "hashinfo": "null",
+        "hash_info": null,
+    })
+}
+
+fn str_field<'a>(body: &'a Value, key: &str) -> &'a str {
+    body.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn names_field(body: &Value, key: &str) -> Vec<String> {
+    body.get(key)
+        .and_then(Value::as_array)
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let fault = {
+        let mut state = state.lock().unwrap();
+        state.requests += 1;
+        let index = state.faults.iter().position(|(p, _)| path.starts_with(p));
+        index.and_then(|i| state.faults.remove(i)).map(|(_, f)| f)
+    };
+    match fault {
+        Some(Fault::Unauthorized) => {
+            return Ok(reply_code(401, "token is expired", Value::Null));
+        }
+        Some(Fault::MalformedJson) => {
+            return Ok(Response::new(Body::from("{\"code\": 200, \"data\": ")));
+        }
+        Some(Fault::ServerError) => {
+            let mut resp = Response::new(Body::from("internal server error"));
+            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            return Ok(resp);
+        }
+        Some(Fault::LinkExpired) => {
+            return Ok(reply_code(401, "sign expired", Value::Null));
+        }
+        Some(Fault::Delay(delay)) => tokio::time::sleep(delay).await,
+        None => {}
+    }
+
+    let method = req.method().clone();
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let file_path = req
+        .headers()
+        .get("File-Path")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| percent_decode_str(v).decode_utf8_lossy().into_owned());
+    let host = req
+        .headers()
+        .get("Host")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("127.0.0.1")
+        .to_string();
+    let range = req
+        .headers()
+        .get("Range")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_range);
+    let bytes = hyper::body::to_bytes(req.into_body())
+        .await
+        .unwrap_or_default();
+
+    if method == Method::GET && path == "/ping" {
+        return Ok(Response::new(Body::from("pong")));
+    }
+    if method == Method::GET && path.starts_with("/d/") {
+        let file = percent_decode_str(&path[2..]).decode_utf8_lossy();
+        let state = state.lock().unwrap();
+        return Ok(match state.nodes.get(&normalize(&file)) {
+            Some(node) if !node.is_dir => {
+                let mut resp = match range {
+                    Some((start, end)) => reply_range(&node.content, start, end),
+                    None => Response::new(Body::from(node.content.clone())),
+                };
+                // 与服务端一致, 按扩展名设置 Content-Type
+                if file.ends_with(".json") {
+                    resp.headers_mut()
+                        .insert("Content-Type", "application/json".parse().unwrap());
+                }
+                resp
+            }
+            _ => reply_code(404, "object not found", Value::Null),
+        });
+    }
+
+    let body: Value = if bytes.is_empty() || method == Method::PUT {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&bytes) {
+            Ok(body) => body,
+            Err(e) => return Ok(reply_code(400, &e.to_string(), Value::Null)),
+        }
+    };
+
+    let mut state = state.lock().unwrap();
+    if (path == "/api/auth/login" || path == "/api/auth/login/hash") && method == Method::POST {
+        // 旧版本服务端只支持明文密码登录
+        let password = if path.ends_with("/hash") {
+            sha256(MockServer::PASSWORD)
+        } else {
+            MockServer::PASSWORD.to_string()
+        };
+        if str_field(&body, "username") != MockServer::USERNAME
+            || str_field(&body, "password") != password
+        {
+            return Ok(reply_code(400, "password is incorrect", Value::Null));
+        }
+        let claims = json!({"username": MockServer::USERNAME, "exp": now() + 48 * 3600});
+        let token = format!(
+            "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.{}.mock{}",
+            URL_SAFE_NO_PAD.encode(claims.to_string()),
+            state.tokens.len()
+        );
+        state.tokens.insert(token.clone());
+        return Ok(reply(json!({ "token": token })));
+    }
+    if path.starts_with("/api/public/") {
+        return Ok(match path.as_str() {
+            "/api/public/settings" => reply(json!(state.settings)),
+            "/api/public/offline_download_tools" => {
+                reply(json!(["aria2", "qBittorrent", "SimpleHttp"]))
+            }
+            _ => reply_index(),
+        });
+    }
+    if !state.tokens.contains(&token) {
+        return Ok(reply_code(
+            401,
+            "Guest user is disabled, login please",
+            Value::Null,
+        ));
+    }
+
+    let data = match (method, path.as_str()) {
+        (Method::GET, "/api/me") => json!({
+            "id": 1,
+            "username": MockServer::USERNAME,
+            "password": "",
+            "base_path": "/",
+            "role": 2,
+            "disabled": false,
+            "permission": 0,
+            "sso_id": "",
+            "otp": false,
+        }),
+        (Method::POST, "/api/fs/list") => {
+            let dir = normalize(str_field(&body, "path"));
+            match state.nodes.get(&dir) {
+                Some(node) if node.is_dir => {}
+                _ => return Ok(reply_code(500, "object not found", Value::Null)),
+            }
+            let all = children(&state, &dir);
+            let total = all.len();
+            let page = body.get("page").and_then(Value::as_u64).unwrap_or(1).max(1) as usize;
+            let per_page = body
+                .get("per_page")
+                .and_then(Value::as_u64)
+                .filter(|n| *n > 0)
+                .map_or(total.max(1), |n| n as usize);
+            let content: Vec<Value> = all
+                .into_iter()
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .map(|(path, node)| dir_entry(path, node))
+                .collect();
+            // 与服务端一致, 空目录返回 null
+            let content = if content.is_empty() {
+                Value::Null
+            } else {
+                json!(content)
+            };
+            json!({
+                "content": content,
+                "total": total,
+                "readme": "",
+                "header": "",
+                "write": true,
+                "provider": "Local",
+            })
+        }
+        (Method::POST, "/api/fs/get") => {
+            let path = normalize(str_field(&body, "path"));
+            let node = match state.nodes.get(&path) {
+                Some(node) => node,
+                None => return Ok(reply_code(500, "object not found", Value::Null)),
+            };
+            let mut entry = dir_entry(&path, node);
+            let raw_url = if node.is_dir {
+                String::new()
+            } else {
+                format!("http://{}/d{}", host, path)
+            };
+            entry["raw_url"] = json!(raw_url);
+            entry["readme"] = json!("");
+            entry["header"] = json!("");
+            entry["provider"] = json!("Local");
+            entry["related"] = Value::Null;
+            entry
+        }
+        (Method::POST, "/api/fs/dirs") => {
+            let dir = normalize(str_field(&body, "path"));
+            let dirs: Vec<Value> = children(&state, &dir)
+                .into_iter()
+                .filter(|(_, node)| node.is_dir)
+                .map(|(path, node)| json!({"name": name(path), "modified": modified(node)}))
+                .collect();
+            json!(dirs)
+        }
+        (Method::POST, "/api/fs/search") => {
+            let parent_dir = normalize(str_field(&body, "parent"));
+            let keywords = str_field(&body, "keywords");
+            let scope = body.get("scope").and_then(Value::as_u64).unwrap_or(0);
+            let prefix = format!("{}/", parent_dir.trim_end_matches('/'));
+            let content: Vec<Value> = state
+                .nodes
+                .iter()
+                .filter(|(path, node)| {
+                    path.starts_with(&prefix)
+                        && name(path).contains(keywords)
+                        && match scope {
+                            1 => node.is_dir,
+                            2 => !node.is_dir,
+                            _ => true,
+                        }
+                })
+                .map(|(path, node)| {
+                    json!({
+                        "parent": split_path(path).0,
+                        "name": name(path),
+                        "is_dir": node.is_dir,
+                        "size": node.content.len(),
+                        "type": if node.is_dir { 1 } else { 0 },
+                    })
+                })
+                .collect();
+            json!({"total": content.len(), "content": content})
+        }
+        (Method::POST, "/api/fs/mkdir") => {
+            let path = normalize(str_field(&body, "path"));
+            create_dirs(&mut state, &path);
+            Value::Null
+        }
+        (Method::POST, "/api/fs/rename") => {
+            let src = normalize(str_field(&body, "path"));
+            let dst = join(&split_path(&src).0, str_field(&body, "name"));
+            if !state.nodes.contains_key(&src) || src == "/" {
+                return Ok(reply_code(500, "object not found", Value::Null));
+            }
+            if state.nodes.contains_key(&dst) {
+                return Ok(reply_code(403, "file exists", Value::Null));
+            }
+            transfer(&mut state, &src, &dst, true);
+            Value::Null
+        }
+        (Method::POST, "/api/fs/batch_rename") => {
+            let dir = normalize(str_field(&body, "src_dir"));
+            let objects = body
+                .get("rename_objects")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            for object in objects {
+                let src = join(&dir, str_field(&object, "src_name"));
+                let dst = join(&dir, str_field(&object, "new_name"));
+                if state.nodes.contains_key(&dst) {
+                    return Ok(reply_code(403, "file exists", Value::Null));
+                }
+                transfer(&mut state, &src, &dst, true);
+            }
+            Value::Null
+        }
+        (Method::POST, "/api/fs/move") | (Method::POST, "/api/fs/copy") => {
+            let src_dir = normalize(str_field(&body, "src_dir"));
+            let dst_dir = normalize(str_field(&body, "dst_dir"));
+            if !state.nodes.get(&dst_dir).is_some_and(|n| n.is_dir) {
+                return Ok(reply_code(500, "object not found", Value::Null));
+            }
+            for name in names_field(&body, "names") {
+                let src = join(&src_dir, &name);
+                if !state.nodes.contains_key(&src) {
+                    return Ok(reply_code(500, "object not found", Value::Null));
+                }
+                transfer(
+                    &mut state,
+                    &src,
+                    &join(&dst_dir, &name),
+                    path == "/api/fs/move",
+                );
+            }
+            Value::Null
+        }
+        (Method::POST, "/api/fs/remove") => {
+            let dir = normalize(str_field(&body, "dir"));
+            for name in names_field(&body, "names") {
+                let target = join(&dir, &name);
+                let prefix = format!("{}/", target);
+                state
+                    .nodes
+                    .retain(|path, _| path != &target && !path.starts_with(&prefix));
+            }
+            Value::Null
+        }
+        (Method::PUT, "/api/fs/put") => {
+            let path = match file_path {
+                Some(path) => normalize(&path),
+                None => return Ok(reply_code(400, "File-Path is required", Value::Null)),
+            };
+            create_dirs(&mut state, &split_path(&path).0);
+            state.nodes.insert(
+                path,
+                Node {
+                    is_dir: false,
+                    content: bytes.to_vec(),
+                    modified: now(),
+                },
+            );
+            Value::Null
+        }
+        (Method::POST, "/api/admin/index/build") | (Method::POST, "/api/admin/index/update") => {
+            // 下一次轮询仍返回构建前的进度
+            state.index_pending = Some((1, build_index(&state, &body)));
+            Value::Null
+        }
+        (Method::POST, "/api/admin/index/stop") => {
+            state.index_pending = None;
+            state.index["is_done"] = json!(true);
+            Value::Null
+        }
+        (Method::POST, "/api/admin/index/clear") => {
+            state.index_pending = None;
+            state.index["obj_count"] = json!(0);
+            Value::Null
+        }
+        (Method::GET, "/api/admin/index/progress") => {
+            match state.index_pending.take() {
+                Some((0, progress)) => state.index = progress,
+                Some((polls, progress)) => state.index_pending = Some((polls - 1, progress)),
+                None => {}
+            }
+            state.index.clone()
+        }
+        // 与旧版本服务端一样只有 aria2 和 qBittorrent 的接口
+        (Method::POST, "/api/fs/add_aria2") | (Method::POST, "/api/fs/add_qbit") => {
+            state.offline_tasks += names_field(&body, "urls").len();
+            Value::Null
+        }
+        _ => return Ok(reply_index()),
+    };
+    Ok(reply(data))
+}
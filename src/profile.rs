@@ -0,0 +1,241 @@
+use super::connection::ConnectionConfig;
+use super::Error;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 配置文件, 默认位于 ~/.config/alistapi/config.toml, 可通过 ALIST_CONFIG 指定
+///
+/// ```toml
+/// default = "home"
+///
+/// [profiles.home]
+/// server = "http://127.0.0.1:5244"
+/// username = "admin"
+/// password = { env = "ALIST_PASSWORD" }
+///
+/// [profiles.nas]
+/// server = "https://nas.example.com"
+/// token = { command = "pass show alist/nas" }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Config {
+    // 未指定名称时使用的配置
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub server: String,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    // 指定后直接使用该 token, 不再登录
+    pub token: Option<Secret>,
+    // 代理和 TLS 设置
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+}
+
+/// 密码或 token 的来源
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Secret {
+    // 明文
+    Value(String),
+    // 环境变量
+    Env(String),
+    // 命令的标准输出, 如 pass show alist
+    Command(String),
+}
+
+impl Secret {
+    /// 获取密码或 token, Command 会阻塞当前线程直到命令结束,
+    /// 在异步代码中应使用 resolve_async
+    pub fn resolve(&self) -> Result<String, Error> {
+        match self {
+            Secret::Command(command) => {
+                let (program, args) = shell(command);
+                let output = std::process::Command::new(program).args(args).output()?;
+                command_output(command, output)
+            }
+            Secret::Value(value) => Ok(value.clone()),
+            Secret::Env(name) => env_var(name),
+        }
+    }
+
+    /// 获取密码或 token, 命令在后台执行, 不阻塞异步运行时
+    #[cfg(feature = "client")]
+    pub async fn resolve_async(&self) -> Result<String, Error> {
+        match self {
+            Secret::Command(command) => {
+                let (program, args) = shell(command);
+                let output = tokio::process::Command::new(program)
+                    .args(args)
+                    .output()
+                    .await?;
+                command_output(command, output)
+            }
+            Secret::Value(value) => Ok(value.clone()),
+            Secret::Env(name) => env_var(name),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Result<String, Error> {
+    std::env::var(name)
+        .map_err(|_| Error::Other(format!("environment variable {} is not set", name)))
+}
+
+fn shell(command: &str) -> (&'static str, [&str; 2]) {
+    if cfg!(windows) {
+        ("cmd", ["/C", command])
+    } else {
+        ("sh", ["-c", command])
+    }
+}
+
+/// 检查退出状态并取出标准输出, 去掉末尾的换行
+fn command_output(command: &str, output: std::process::Output) -> Result<String, Error> {
+    if !output.status.success() {
+        return Err(Error::Other(format!(
+            "command `{}` failed: {}",
+            command, output.status
+        )));
+    }
+    let value = String::from_utf8(output.stdout)
+        .map_err(|_| Error::Other(format!("command `{}` output is not utf-8", command)))?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf, Error> {
+        if let Some(path) = std::env::var_os("ALIST_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        dirs::config_dir()
+            .map(|dir| dir.join("alistapi").join("config.toml"))
+            .ok_or_else(|| Error::Other("config directory not found".to_string()))
+    }
+
+    /// 读取配置文件, 文件不存在时返回空配置
+    pub fn load() -> Result<Self, Error> {
+        let path = Config::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Config::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        toml::from_str(content).map_err(|e| Error::Other(format!("invalid config: {}", e)))
+    }
+
+    /// 按名称获取配置, 名称为空时使用 default, 返回实际使用的名称
+    pub fn profile<'a>(&'a self, name: Option<&'a str>) -> Result<(&'a str, &'a Profile), Error> {
+        let name = name
+            .or(self.default.as_deref())
+            .ok_or_else(|| Error::Other("no profile specified".to_string()))?;
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| Error::Other(format!("profile {} not found", name)))?;
+        Ok((name, profile))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedToken {
+    pub server: String,
+    pub username: String,
+    pub token: String,
+    // 过期时间戳(秒), 取自 token 的 exp
+    pub expires_at: u64,
+}
+
+/// 登录 token 缓存, 位于 ~/.cache/alistapi/tokens.json
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TokenCache {
+    tokens: HashMap<String, CachedToken>,
+}
+
+impl TokenCache {
+    pub fn path() -> Result<PathBuf, Error> {
+        dirs::cache_dir()
+            .map(|dir| dir.join("alistapi").join("tokens.json"))
+            .ok_or_else(|| Error::Other("cache directory not found".to_string()))
+    }
+
+    /// 读取缓存, 缓存损坏时视为空
+    pub fn load() -> Self {
+        TokenCache::path()
+            .ok()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        self.save_to(&TokenCache::path()?)
+    }
+
+    /// 写入 path, 只有当前用户可以读写
+    pub(crate) fn save_to(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_vec_pretty(self).map_err(|e| Error::Other(e.to_string()))?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // mode 只在新建文件时生效, 已有的文件也要在写入 token 前修改权限
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        std::io::Write::write_all(&mut file, &content)?;
+        Ok(())
+    }
+
+    /// 获取未过期且与服务端和用户名匹配的 token
+    pub fn get(&self, profile: &str, server: &str, username: &str) -> Option<&str> {
+        // 预留一分钟, 避免请求途中过期
+        let now = unix_now() + 60;
+        self.tokens
+            .get(profile)
+            .filter(|t| t.server == server && t.username == username && t.expires_at > now)
+            .map(|t| t.token.as_str())
+    }
+
+    pub fn insert(&mut self, profile: &str, token: CachedToken) {
+        self.tokens.insert(profile.to_string(), token);
+    }
+
+    pub fn remove(&mut self, profile: &str) {
+        self.tokens.remove(profile);
+    }
+}
+
+/// 解析 jwt 中的 exp
+pub fn token_expires_at(token: &str) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct Claims {
+        exp: u64,
+    }
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<Claims>(&payload)
+        .ok()
+        .map(|c| c.exp)
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
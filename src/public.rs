@@ -1,36 +1,33 @@
 use serde::{Deserialize, Serialize};
 
-use super::{NullResponse, Response};
+#[cfg(feature = "client")]
+use super::{capability::Capability, client::ApiRequest, AlistClient, Error, Response};
 
 /// ping检测 GET /ping
-pub async fn ping(server: &str) -> Result<(), String> {
-    let url = format!("{}/ping", server);
-    let resp: Response<NullResponse> = reqwest::Client::new()
-        .get(url)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
-    }
-    Ok(())
+#[cfg(feature = "client")]
+pub async fn ping(server: &str) -> Result<(), Error> {
+    AlistClient::new(server).ping().await
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// 旧版本服务端没有的设置项标记为 default, 缺少时为空字符串,
+// 其它设置项缺少时解析失败, 以便发现接口变化
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Settings {
     pub allow_indexed: String,
     pub allow_mounted: String,
     pub announcement: String,
+    // 可在线预览/解压的压缩包后缀
+    #[serde(default)]
+    pub archive_extensions: String,
     pub audio_autoplay: String,
     pub audio_cover: String,
     pub auto_update_index: String,
     pub default_page_size: String,
     pub external_previews: String,
     pub favicon: String,
+    #[serde(default)]
     pub filename_char_mapping: String,
+    #[serde(default)]
     pub forward_direct_link_params: String,
     pub hide_files: String,
     pub home_container: String,
@@ -46,24 +43,66 @@ pub struct Settings {
     pub settings_layout: String,
     pub site_title: String,
     pub sso_login_enabled: String,
+    #[serde(default)]
     pub sso_login_platform: String,
     pub version: String,
     pub video_autoplay: String,
 }
 
+impl Settings {
+    /// 是否可以使用 archive 接口浏览/解压该文件
+    pub fn is_archive(&self, name: &str) -> bool {
+        let ext = match name.rsplit_once('.') {
+            Some((_, ext)) => ext,
+            None => return false,
+        };
+        self.archive_extensions
+            .split(',')
+            .map(str::trim)
+            .any(|e| e.eq_ignore_ascii_case(ext))
+    }
+}
+
 /// 获取站点设置 GET /api/public/settings
-pub async fn get_settings(server: &str) -> Result<Settings, String> {
-    let url = format!("{}/api/public/settings", server);
-    let resp: Response<Settings> = reqwest::Client::new()
-        .get(url)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    if resp.code != 200 {
-        return Err(resp.message);
+#[cfg(feature = "client")]
+pub async fn get_settings(server: &str) -> Result<Settings, Error> {
+    AlistClient::new(server).get_settings().await
+}
+
+/// 获取已配置的离线下载工具 GET /api/public/offline_download_tools
+#[cfg(feature = "client")]
+pub async fn offline_download_tools(server: &str) -> Result<Vec<String>, Error> {
+    AlistClient::new(server).offline_download_tools().await
+}
+
+#[cfg(feature = "client")]
+impl AlistClient {
+    /// ping检测 GET /ping
+    ///
+    /// 服务端返回纯文本 pong
+    pub async fn ping(&self) -> Result<(), Error> {
+        let (status, body) = self.send(ApiRequest::get("/ping")).await?;
+        if body.trim_ascii() == b"pong" {
+            return Ok(());
+        }
+        match serde_json::from_slice::<Response<serde_json::Value>>(&body) {
+            Ok(resp) => resp.check().map(|_| ()),
+            Err(_) if !(200..300).contains(&status) => Err(Error::Status(status)),
+            Err(e) => Err(Error::Decode(e)),
+        }
+    }
+
+    /// 获取站点设置 GET /api/public/settings
+    pub async fn get_settings(&self) -> Result<Settings, Error> {
+        self.call(ApiRequest::get("/api/public/settings")).await
+    }
+
+    /// 获取已配置的离线下载工具 GET /api/public/offline_download_tools
+    pub async fn offline_download_tools(&self) -> Result<Vec<String>, Error> {
+        self.require(Capability::OfflineDownload)?;
+        let resp = self
+            .execute::<Vec<String>>(ApiRequest::get("/api/public/offline_download_tools"))
+            .await?;
+        Ok(resp.check()?.unwrap_or_default())
     }
-    Ok(resp.data.unwrap())
 }
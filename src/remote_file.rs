@@ -0,0 +1,232 @@
+//! 随机读取远程文件, 通过 HTTP Range 请求 fs/get 返回的 raw_url, 不需要下载整个文件
+//!
+//! ```no_run
+//! use alistapi::remote_file::OpenOptions;
+//! use alistapi::AlistClient;
+//! use std::io::SeekFrom;
+//! use tokio::io::{AsyncReadExt, AsyncSeekExt};
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! let mut file = client
+//!     .open_with("/cloud/archive.zip", OpenOptions { read_ahead: 64 * 1024 })
+//!     .await?;
+//! // 读取 zip 末尾的目录记录
+//! file.seek(SeekFrom::End(-22)).await?;
+//! let mut eocd = [0u8; 22];
+//! file.read_exact(&mut eocd).await?;
+//! # Ok(())
+//! # }
+//! ```
+use super::client::AlistClient;
+use super::fs::FileParams;
+use super::middleware::BoxFuture;
+use super::trace::RequestSpan;
+use super::Error;
+use bytes::{Bytes, BytesMut};
+use std::io::{self, SeekFrom};
+use std::ops::Range;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    // 每次请求至少读取的字节数, 后续的顺序读取可以直接使用缓存
+    pub read_ahead: usize,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions {
+            read_ahead: 256 * 1024,
+        }
+    }
+}
+
+type Fetch = BoxFuture<'static, Result<(String, Bytes), Error>>;
+
+/// 远程文件的只读句柄, 实现 AsyncRead 和 AsyncSeek
+///
+/// 下载链接的签名过期时会重新获取 raw_url.
+pub struct RemoteFile {
+    client: AlistClient,
+    path: String,
+    url: String,
+    len: u64,
+    pos: u64,
+    read_ahead: usize,
+    // 缓存的内容及其在文件中的起始位置
+    buffer: Bytes,
+    buffer_start: u64,
+    // 进行中的请求及其起始位置
+    fetch: Option<(u64, Fetch)>,
+}
+
+impl RemoteFile {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 文件大小
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl AlistClient {
+    /// 打开远程文件用于随机读取
+    pub async fn open(&self, path: &str) -> Result<RemoteFile, Error> {
+        self.open_with(path, OpenOptions::default()).await
+    }
+
+    pub async fn open_with(&self, path: &str, options: OpenOptions) -> Result<RemoteFile, Error> {
+        let info = self
+            .fileinfo(FileParams {
+                path: Some(path.to_string()),
+                ..Default::default()
+            })
+            .await?;
+        if info.is_dir {
+            return Err(Error::Other(format!("{} is a directory", info.name)));
+        }
+        Ok(RemoteFile {
+            client: self.clone(),
+            path: path.to_string(),
+            url: info.row_url,
+            len: u64::try_from(info.size).unwrap_or(u64::MAX),
+            pos: 0,
+            read_ahead: options.read_ahead.max(1),
+            buffer: Bytes::new(),
+            buffer_start: 0,
+            fetch: None,
+        })
+    }
+
+    /// 读取 raw_url 的一部分, raw_url 可能指向第三方存储, 不能携带 token
+    async fn read_range(&self, url: &str, range: Range<u64>) -> Result<Bytes, Error> {
+        let span = RequestSpan::download(url);
+        let mut resp = self.get_raw(url, false, Some(range.clone()), &span).await?;
+        // 不支持 Range 的服务端返回完整内容, 只有从头读取时可以使用
+        if resp.status() != 206 && range.start != 0 {
+            return Err(Error::Other(
+                "server does not support range requests".to_string(),
+            ));
+        }
+        let len = (range.end - range.start) as usize;
+        let mut data = BytesMut::with_capacity(len);
+        while data.len() < len {
+            match self.read_chunk(&mut resp).await? {
+                Some(chunk) => data.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        data.truncate(len);
+        span.received(data.len() as u64);
+        Ok(data.freeze())
+    }
+}
+
+/// 签名过期或失效的下载链接
+fn is_expired(err: &Error) -> bool {
+    matches!(err, Error::Status(401 | 403 | 410)) || matches!(err.code(), Some(401 | 403))
+}
+
+/// 读取失败且链接已过期时重新获取 raw_url, 返回使用的链接和内容
+async fn fetch(
+    client: AlistClient,
+    path: String,
+    url: String,
+    range: Range<u64>,
+) -> Result<(String, Bytes), Error> {
+    match client.read_range(&url, range.clone()).await {
+        Err(err) if is_expired(&err) => {
+            let info = client
+                .fileinfo(FileParams {
+                    path: Some(path),
+                    ..Default::default()
+                })
+                .await?;
+            let data = client.read_range(&info.row_url, range).await?;
+            Ok((info.row_url, data))
+        }
+        result => result.map(|data| (url, data)),
+    }
+}
+
+impl AsyncRead for RemoteFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.pos >= this.len || buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            let buffer_end = this.buffer_start + this.buffer.len() as u64;
+            if (this.buffer_start..buffer_end).contains(&this.pos) {
+                let offset = (this.pos - this.buffer_start) as usize;
+                let n = buf.remaining().min(this.buffer.len() - offset);
+                buf.put_slice(&this.buffer[offset..offset + n]);
+                this.pos += n as u64;
+                return Poll::Ready(Ok(()));
+            }
+
+            // seek 后之前的请求不再需要
+            if this
+                .fetch
+                .as_ref()
+                .is_none_or(|(start, _)| *start != this.pos)
+            {
+                let size = this.read_ahead.max(buf.remaining()) as u64;
+                let range = this.pos..this.len.min(this.pos + size);
+                let fut = fetch(
+                    this.client.clone(),
+                    this.path.clone(),
+                    this.url.clone(),
+                    range,
+                );
+                this.fetch = Some((this.pos, Box::pin(fut)));
+            }
+            let (start, fut) = this.fetch.as_mut().unwrap();
+            let start = *start;
+            let result = ready!(fut.as_mut().poll(cx));
+            this.fetch = None;
+            let (url, data) = result?;
+            if data.is_empty() {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.url = url;
+            this.buffer = data;
+            this.buffer_start = start;
+        }
+    }
+}
+
+impl AsyncSeek for RemoteFile {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let pos = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => this.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => this.pos.checked_add_signed(offset),
+        };
+        this.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
@@ -0,0 +1,115 @@
+use super::{Error, Response};
+use serde::de::IgnoredAny;
+use std::time::Duration;
+
+/// 请求失败时的重试策略
+///
+/// 只读接口(listdir/fileinfo/search/get_dirs/get_settings 等)会自动重试,
+/// 修改服务端文件的接口只有在 retry_mutating 为 true 时才会重试.
+/// 指定 refresh 的 listdir 每次都会刷新存储, 视为修改服务端的请求.
+/// 超时的请求可能已在服务端执行, 只有只读接口会在超时后重试.
+///
+/// ```
+/// use alistapi::{retry::RetryPolicy, AlistClient};
+/// use std::time::Duration;
+///
+/// let client = AlistClient::new("http://127.0.0.1:5244").with_retry(RetryPolicy {
+///     max_attempts: 5,
+///     initial_backoff: Duration::from_millis(500),
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // 最多尝试次数, 包括第一次请求, 1 表示不重试
+    pub max_attempts: u32,
+    // 第一次重试前的等待时间
+    pub initial_backoff: Duration,
+    // 等待时间上限
+    pub max_backoff: Duration,
+    // 每次重试后等待时间的倍数
+    pub multiplier: f64,
+    // 随机等待 [backoff/2, backoff], 避免大量客户端同时重试
+    pub jitter: bool,
+    // 需要重试的 HTTP 状态码
+    pub retry_status: Vec<u16>,
+    // 需要重试的接口 code, 服务端对不存在的文件等也返回 500, 默认不重试
+    pub retry_codes: Vec<isize>,
+    // 是否重试会修改服务端的请求, 如 move/remove/upload, 超时的请求除外
+    pub retry_mutating: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: true,
+            retry_status: vec![408, 429, 500, 502, 503, 504],
+            retry_codes: Vec::new(),
+            retry_mutating: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// 第 attempt 次请求失败后的等待时间, attempt 从 1 开始
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let backoff = self
+            .initial_backoff
+            .mul_f64(exp.max(0.0))
+            .min(self.max_backoff);
+        if self.jitter {
+            backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0)
+        } else {
+            backoff
+        }
+    }
+
+    /// 是否还可以继续重试
+    pub(crate) fn allows(&self, attempt: u32, idempotent: bool) -> bool {
+        attempt < self.max_attempts && (idempotent || self.retry_mutating)
+    }
+
+    /// 响应的 HTTP 状态码或接口 code 是否需要重试
+    pub(crate) fn is_retryable_response(&self, status: u16, body: &[u8]) -> bool {
+        if self.retry_status.contains(&status) {
+            return true;
+        }
+        !self.retry_codes.is_empty()
+            && serde_json::from_slice::<Response<IgnoredAny>>(body)
+                .is_ok_and(|resp| self.retry_codes.contains(&resp.code))
+    }
+
+    /// 请求出错后是否需要重试, 修改服务端的请求超时后不重试
+    pub(crate) fn should_retry_error(&self, err: &Error, idempotent: bool) -> bool {
+        let timeout = match err {
+            Error::Http(e) => e.is_timeout(),
+            Error::Timeout => true,
+            _ => false,
+        };
+        (idempotent || !timeout) && self.is_retryable_error(err)
+    }
+
+    /// 网络错误中连接失败和超时可以重试, 取消的请求不会重试
+    pub fn is_retryable_error(&self, err: &Error) -> bool {
+        match err {
+            Error::Http(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            Error::Timeout => true,
+            Error::Status(status) => self.retry_status.contains(status),
+            Error::Api { code, .. } => self.retry_codes.contains(code),
+            _ => false,
+        }
+    }
+}
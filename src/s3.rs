@@ -0,0 +1,705 @@
+//! 本地 S3 兼容网关, 需要启用 s3 feature
+//!
+//! 每个 bucket 对应 alist 上的一个目录, 对象的 key 为相对该目录的路径. 只支持 path-style 访问
+//! (`http://host/bucket/key`) 和 ListObjectsV2、GetObject、HeadObject、PutObject、CopyObject、
+//! DeleteObject, 不支持分片上传. 网关不校验请求签名, 客户端可以使用任意凭证, 只应监听本地地址.
+//!
+//! ```no_run
+//! use alistapi::s3::S3Server;
+//! use alistapi::AlistClient;
+//! use std::collections::BTreeMap;
+//!
+//! # async fn run() -> Result<(), alistapi::Error> {
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! let buckets = BTreeMap::from([("media".to_string(), "/cloud/media".to_string())]);
+//! let server = S3Server::start(client, buckets, ([127, 0, 0, 1], 9000).into()).await?;
+//! // aws s3 ls s3://media/ --endpoint-url http://127.0.0.1:9000
+//! println!("S3 endpoint: {}", server.url());
+//! # Ok(())
+//! # }
+//! ```
+use super::client::AlistClient;
+use super::gateway::{escape, is_not_found, HttpServer};
+use super::remote_file::OpenOptions;
+use super::time::format_rfc3339;
+use super::vfs::{copy_as, join_path, split_path, Metadata, RemoteFs};
+use super::Error;
+use bytes::BytesMut;
+use hyper::body::HttpBody;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::SeekFrom;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+// encoding-type=url 时 key 中需要编码的字符, 保留 /
+const KEY: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+const XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+// 一次 ListObjectsV2 最多返回的数量
+const MAX_KEYS: usize = 1000;
+
+/// 在后台运行的 S3 服务, drop 时停止
+pub struct S3Server {
+    server: HttpServer,
+}
+
+struct Gateway {
+    client: AlistClient,
+    // bucket 名称 -> alist 目录
+    buckets: BTreeMap<String, String>,
+}
+
+impl S3Server {
+    /// 监听 addr, 端口为 0 时随机选择, buckets 为 bucket 名称到 alist 目录的映射
+    pub async fn start(
+        client: AlistClient,
+        buckets: BTreeMap<String, String>,
+        addr: SocketAddr,
+    ) -> Result<S3Server, Error> {
+        let gateway = Arc::new(Gateway { client, buckets });
+        let server = HttpServer::start(addr, move |req| handle(gateway.clone(), req))?;
+        Ok(S3Server { server })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    /// 客户端使用的 endpoint url
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+}
+
+/// S3 格式的错误响应
+#[derive(Debug)]
+struct S3Error {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        S3Error {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn no_such_key() -> Self {
+        S3Error::new(
+            StatusCode::NOT_FOUND,
+            "NoSuchKey",
+            "The specified key does not exist.",
+        )
+    }
+
+    fn not_implemented() -> Self {
+        S3Error::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "This operation is not supported by the alist gateway.",
+        )
+    }
+
+    fn into_response(self, resource: &str) -> Response<Body> {
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource></Error>",
+            self.code,
+            escape(&self.message),
+            escape(resource)
+        );
+        let mut resp = Response::new(Body::from(xml));
+        *resp.status_mut() = self.status;
+        resp.headers_mut()
+            .insert("Content-Type", "application/xml".parse().unwrap());
+        resp
+    }
+}
+
+impl From<Error> for S3Error {
+    fn from(err: Error) -> Self {
+        let (status, code) = match &err {
+            err if is_not_found(err) => (StatusCode::NOT_FOUND, "NoSuchKey"),
+            Error::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                (StatusCode::FORBIDDEN, "AccessDenied")
+            }
+            Error::Api {
+                code: 401 | 403, ..
+            } => (StatusCode::FORBIDDEN, "AccessDenied"),
+            Error::Other(_) | Error::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "InternalError"),
+            // 连接 alist 失败等, 客户端会重试 503
+            _ => (StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable"),
+        };
+        S3Error::new(status, code, err.to_string())
+    }
+}
+
+impl From<std::io::Error> for S3Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::from(err).into()
+    }
+}
+
+async fn handle(gateway: Arc<Gateway>, req: Request<Body>) -> Response<Body> {
+    let resource = req.uri().path().to_string();
+    dispatch(&gateway, req)
+        .await
+        .unwrap_or_else(|err| err.into_response(&resource))
+}
+
+async fn dispatch(gateway: &Gateway, req: Request<Body>) -> Result<Response<Body>, S3Error> {
+    let path = decode(req.uri().path());
+    let query = parse_query(req.uri().query().unwrap_or(""));
+    let path = path.trim_start_matches('/');
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    if bucket.is_empty() {
+        return match *req.method() {
+            Method::GET => Ok(list_buckets(gateway)),
+            _ => Err(S3Error::not_implemented()),
+        };
+    }
+    let root = gateway.bucket(bucket)?;
+    // 分片上传
+    if query.contains_key("uploads") || query.contains_key("uploadId") {
+        return Err(S3Error::not_implemented());
+    }
+    if key.is_empty() {
+        return match *req.method() {
+            Method::GET => list_objects(gateway, bucket, root, &query).await,
+            Method::HEAD => Ok(empty(StatusCode::OK)),
+            _ => Err(S3Error::not_implemented()),
+        };
+    }
+    let object = object_path(root, key)?;
+    match *req.method() {
+        Method::GET | Method::HEAD => get_object(gateway, key, &object, &req).await,
+        Method::PUT if req.headers().contains_key("x-amz-copy-source") => {
+            copy_object(gateway, &object, &req).await
+        }
+        Method::PUT => put_object(gateway, key, &object, req).await,
+        Method::DELETE => delete_object(gateway, key, &object).await,
+        _ => Err(S3Error::not_implemented()),
+    }
+}
+
+impl Gateway {
+    fn fs(&self) -> &dyn RemoteFs {
+        &self.client
+    }
+
+    fn bucket(&self, name: &str) -> Result<&str, S3Error> {
+        self.buckets.get(name).map(String::as_str).ok_or_else(|| {
+            S3Error::new(
+                StatusCode::NOT_FOUND,
+                "NoSuchBucket",
+                "The specified bucket does not exist.",
+            )
+        })
+    }
+}
+
+/// 对象在 alist 上的路径, 以 / 结尾的 key 表示目录
+///
+/// alist 会整理路径中的 . 和 .., 包含这些片段的 key 可能访问到 bucket 目录之外
+fn object_path(root: &str, key: &str) -> Result<String, S3Error> {
+    if key
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+    {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidArgument",
+            "Object key must not contain . or .. segments.",
+        ));
+    }
+    Ok(match key.trim_matches('/') {
+        "" => root.to_string(),
+        key => join_path(root, key),
+    })
+}
+
+fn list_buckets(gateway: &Gateway) -> Response<Body> {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListAllMyBucketsResult xmlns=\"{}\"><Owner><ID>alist</ID><DisplayName>alist</DisplayName></Owner><Buckets>",
+        XMLNS
+    );
+    for name in gateway.buckets.keys() {
+        let _ = write!(
+            xml,
+            "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+            escape(name),
+            format_rfc3339(UNIX_EPOCH, 3)
+        );
+    }
+    xml.push_str("</Buckets></ListAllMyBucketsResult>");
+    xml_response(StatusCode::OK, xml)
+}
+
+/// ListObjectsV2, continuation-token 为上一页最后一个 key
+async fn list_objects(
+    gateway: &Gateway,
+    bucket: &str,
+    root: &str,
+    query: &HashMap<String, String>,
+) -> Result<Response<Body>, S3Error> {
+    let param = |name: &str| query.get(name).map_or("", String::as_str);
+    let (prefix, delimiter) = (param("prefix"), param("delimiter"));
+    let max_keys = query
+        .get("max-keys")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(MAX_KEYS)
+        .min(MAX_KEYS);
+    let token = query.get("continuation-token");
+    let start_after = token.map_or(param("start-after"), String::as_str);
+    let encode = |value: &str| match param("encoding-type") {
+        "url" => escape(&utf8_percent_encode(value, KEY).to_string()),
+        _ => escape(value),
+    };
+
+    // 多取一个用于判断是否还有下一页
+    let mut page = collect_keys(
+        gateway.fs(),
+        root,
+        prefix,
+        delimiter,
+        start_after,
+        max_keys + 1,
+    )
+    .await?;
+    let truncated = page.len() > max_keys;
+    page.truncate(max_keys);
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"{}\"><Name>{}</Name><Prefix>{}</Prefix>",
+        XMLNS,
+        escape(bucket),
+        encode(prefix)
+    );
+    if !delimiter.is_empty() {
+        let _ = write!(xml, "<Delimiter>{}</Delimiter>", encode(delimiter));
+    }
+    if param("encoding-type") == "url" {
+        xml.push_str("<EncodingType>url</EncodingType>");
+    }
+    let _ = write!(
+        xml,
+        "<MaxKeys>{}</MaxKeys><KeyCount>{}</KeyCount><IsTruncated>{}</IsTruncated>",
+        max_keys,
+        page.len(),
+        truncated
+    );
+    if let Some(token) = token {
+        let _ = write!(
+            xml,
+            "<ContinuationToken>{}</ContinuationToken>",
+            escape(token)
+        );
+    }
+    if let (true, Some((last, _))) = (truncated, page.last()) {
+        let _ = write!(
+            xml,
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            escape(last)
+        );
+    }
+    for (key, meta) in &page {
+        match meta {
+            Some(meta) => {
+                let _ = write!(
+                    xml,
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                    encode(key),
+                    format_rfc3339(meta.modified.unwrap_or(UNIX_EPOCH), 3),
+                    escape(&etag(meta)),
+                    meta.size
+                );
+            }
+            None => {
+                let _ = write!(
+                    xml,
+                    "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                    encode(key)
+                );
+            }
+        }
+    }
+    xml.push_str("</ListBucketResult>");
+    Ok(xml_response(StatusCode::OK, xml))
+}
+
+/// 按 key 的顺序列出以 prefix 开头且大于 start_after 的对象, 最多 limit 个,
+/// 值为 None 的是按 delimiter 合并的公共前缀
+///
+/// 子目录按 key 排序后深度优先遍历, 得到的 key 也是有序的, 因此可以跳过 start_after
+/// 之前的目录, 取够 limit 个后停止, 翻页时不需要重新列出整个 bucket
+async fn collect_keys(
+    fs: &dyn RemoteFs,
+    root: &str,
+    prefix: &str,
+    delimiter: &str,
+    start_after: &str,
+    limit: usize,
+) -> Result<Vec<(String, Option<Metadata>)>, S3Error> {
+    let mut result: Vec<(String, Option<Metadata>)> = Vec::new();
+    // 从 prefix 所在的目录开始, 只进入可能包含匹配对象的子目录
+    let start = prefix.rfind('/').map_or("", |i| &prefix[..=i]);
+    let mut pending = vec![sorted_keys(fs, root, start).await?.into_iter()];
+    while let Some(entries) = pending.last_mut() {
+        if result.len() >= limit {
+            break;
+        }
+        let Some((key, meta)) = entries.next() else {
+            pending.pop();
+            continue;
+        };
+        if !key.starts_with(prefix) {
+            if meta.is_dir && prefix.starts_with(&key) {
+                pending.push(sorted_keys(fs, root, &key).await?.into_iter());
+            }
+            continue;
+        }
+        let common = match delimiter {
+            "" => None,
+            delimiter => key[prefix.len()..].find(delimiter),
+        };
+        if let Some(i) = common {
+            // 合并到同一前缀的 key 是连续的, 只需与上一个比较
+            let common = &key[..prefix.len() + i + delimiter.len()];
+            let added = result.last().is_some_and(|(last, _)| last == common);
+            if common > start_after && !added {
+                result.push((common.to_string(), None));
+            }
+        } else if meta.is_dir {
+            // 目录下的 key 都以它开头, 整个目录都不大于 start_after 时跳过
+            if key.as_str() > start_after || start_after.starts_with(&key) {
+                pending.push(sorted_keys(fs, root, &key).await?.into_iter());
+            }
+        } else if key.as_str() > start_after {
+            result.push((key, Some(meta)));
+        }
+    }
+    Ok(result)
+}
+
+/// 列出 dir 下的 key, 目录以 / 结尾, 按 key 排序
+async fn sorted_keys(
+    fs: &dyn RemoteFs,
+    root: &str,
+    dir: &str,
+) -> Result<Vec<(String, Metadata)>, S3Error> {
+    let entries = match fs.read_dir(&object_path(root, dir)?).await {
+        Ok(entries) => entries,
+        Err(err) if is_not_found(&err) => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut keys: Vec<_> = entries
+        .into_iter()
+        .map(|meta| {
+            let mut key = format!("{}{}", dir, meta.name);
+            if meta.is_dir {
+                key.push('/');
+            }
+            (key, meta)
+        })
+        .collect();
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(keys)
+}
+
+async fn get_object(
+    gateway: &Gateway,
+    key: &str,
+    path: &str,
+    req: &Request<Body>,
+) -> Result<Response<Body>, S3Error> {
+    let meta = gateway.fs().metadata(path).await?;
+    // 目录只能通过以 / 结尾的 key 访问
+    if meta.is_dir != key.ends_with('/') {
+        return Err(S3Error::no_such_key());
+    }
+    let mut resp = Response::builder()
+        .header("Accept-Ranges", "bytes")
+        .header("ETag", etag(&meta))
+        .header("Content-Type", "application/octet-stream");
+    if let Some(modified) = meta.modified {
+        resp = resp.header("Last-Modified", httpdate::fmt_http_date(modified));
+    }
+    let range = match req.headers().get("Range").and_then(|v| v.to_str().ok()) {
+        Some(value) if !meta.is_dir => parse_range(value, meta.size)?,
+        _ => None,
+    };
+    let head = req.method() == Method::HEAD;
+    let Some(range) = range else {
+        let body = if head || meta.is_dir {
+            Body::empty()
+        } else {
+            Body::wrap_stream(ReaderStream::new(gateway.fs().open_read(path).await?))
+        };
+        return Ok(resp.header("Content-Length", meta.size).body(body).unwrap());
+    };
+    let len = range.end - range.start;
+    resp = resp
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header("Content-Length", len)
+        .header(
+            "Content-Range",
+            format!("bytes {}-{}/{}", range.start, range.end - 1, meta.size),
+        );
+    if head {
+        return Ok(resp.body(Body::empty()).unwrap());
+    }
+    let options = OpenOptions {
+        read_ahead: len.min(4 * 1024 * 1024) as usize,
+    };
+    let mut file = gateway.client.open_with(path, options).await?;
+    file.seek(SeekFrom::Start(range.start)).await?;
+    let body = Body::wrap_stream(ReaderStream::new(file.take(len)));
+    Ok(resp.body(body).unwrap())
+}
+
+/// 解析 Range 请求头, 不支持的格式按读取整个文件处理
+fn parse_range(value: &str, size: u64) -> Result<Option<Range<u64>>, S3Error> {
+    let Some((start, end)) = value
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.trim().split_once('-'))
+    else {
+        return Ok(None);
+    };
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => start..size.min(end.saturating_add(1)),
+        (Ok(start), Err(_)) if end.is_empty() => start..size,
+        (Err(_), Ok(suffix)) if start.is_empty() => size.saturating_sub(suffix)..size,
+        _ => return Ok(None),
+    };
+    if range.start >= range.end {
+        return Err(S3Error::new(
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            "InvalidRange",
+            "The requested range is not satisfiable.",
+        ));
+    }
+    Ok(Some(range))
+}
+
+async fn put_object(
+    gateway: &Gateway,
+    key: &str,
+    path: &str,
+    req: Request<Body>,
+) -> Result<Response<Body>, S3Error> {
+    let fs = gateway.fs();
+    if key.ends_with('/') {
+        fs.create_dir(path).await?;
+    } else {
+        // aws cli 和新版 SDK 默认使用 aws-chunked 编码上传
+        let chunked = req
+            .headers()
+            .get("x-amz-content-sha256")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("STREAMING-"));
+        let mut decoder = chunked.then(AwsChunked::default);
+        let mut writer = fs.create_write(path).await?;
+        let mut body = req.into_body();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|e| Error::Other(e.to_string()))?;
+            match &mut decoder {
+                Some(decoder) => writer.write_all(&decoder.feed(&chunk)?).await?,
+                None => writer.write_all(&chunk).await?,
+            }
+        }
+        if decoder.is_some_and(|decoder| !decoder.done) {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "IncompleteBody",
+                "The request body terminated unexpectedly.",
+            ));
+        }
+        writer.shutdown().await?;
+    }
+    let meta = fs.metadata(path).await?;
+    Ok(Response::builder()
+        .header("ETag", etag(&meta))
+        .body(Body::empty())
+        .unwrap())
+}
+
+async fn copy_object(
+    gateway: &Gateway,
+    path: &str,
+    req: &Request<Body>,
+) -> Result<Response<Body>, S3Error> {
+    let fs = gateway.fs();
+    let source = req
+        .headers()
+        .get("x-amz-copy-source")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    // 格式为 [/]bucket/key[?versionId=..]
+    let source = decode(source.split('?').next().unwrap_or_default());
+    let Some((bucket, key)) = source.trim_start_matches('/').split_once('/') else {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidArgument",
+            "Copy Source must mention the source bucket and key.",
+        ));
+    };
+    let source = object_path(gateway.bucket(bucket)?, key)?;
+    if fs.metadata(&source).await?.is_dir {
+        return Err(S3Error::no_such_key());
+    }
+    if source != path {
+        match fs.metadata(path).await {
+            Ok(existing) if existing.is_dir => {
+                return Err(S3Error::new(
+                    StatusCode::CONFLICT,
+                    "InvalidRequest",
+                    "The destination key is a directory.",
+                ));
+            }
+            Ok(_) => fs.remove(path).await?,
+            Err(_) => {
+                let (dir, _) = split_path(path);
+                if fs.metadata(&dir).await.is_err() {
+                    fs.create_dir(&dir).await?;
+                }
+            }
+        }
+        copy_as(fs, &source, path).await?;
+    }
+    let meta = fs.metadata(path).await?;
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<CopyObjectResult xmlns=\"{}\"><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+        XMLNS,
+        format_rfc3339(meta.modified.unwrap_or(UNIX_EPOCH), 3),
+        escape(&etag(&meta))
+    );
+    Ok(xml_response(StatusCode::OK, xml))
+}
+
+/// 删除不存在的对象也返回成功, 以 / 结尾的 key 只删除空目录
+async fn delete_object(
+    gateway: &Gateway,
+    key: &str,
+    path: &str,
+) -> Result<Response<Body>, S3Error> {
+    let fs = gateway.fs();
+    let meta = match fs.metadata(path).await {
+        Ok(meta) => meta,
+        Err(err) if is_not_found(&err) => return Ok(empty(StatusCode::NO_CONTENT)),
+        Err(err) => return Err(err.into()),
+    };
+    let removable = match (meta.is_dir, key.ends_with('/')) {
+        (false, false) => true,
+        (true, true) => fs.read_dir(path).await?.is_empty(),
+        _ => false,
+    };
+    if removable {
+        fs.remove(path).await?;
+    }
+    Ok(empty(StatusCode::NO_CONTENT))
+}
+
+/// 解码 aws-chunked 请求体, 每块为 `十六进制大小[;chunk-signature=..]\r\n数据\r\n`,
+/// 以大小为 0 的块和可选的 trailer 结束
+#[derive(Default)]
+struct AwsChunked {
+    buffer: BytesMut,
+    // 当前块未读取的字节数
+    remaining: usize,
+    done: bool,
+}
+
+impl AwsChunked {
+    fn feed(&mut self, data: &[u8]) -> Result<Vec<u8>, S3Error> {
+        self.buffer.extend_from_slice(data);
+        let mut out = Vec::new();
+        while !self.done {
+            if self.remaining > 0 {
+                if self.buffer.is_empty() {
+                    break;
+                }
+                let n = self.remaining.min(self.buffer.len());
+                out.extend_from_slice(&self.buffer.split_to(n));
+                self.remaining -= n;
+                continue;
+            }
+            let Some(end) = self.buffer.windows(2).position(|w| w == b"\r\n") else {
+                break;
+            };
+            let line = self.buffer.split_to(end + 2);
+            // 上一块数据后的空行
+            if end == 0 {
+                continue;
+            }
+            let size = std::str::from_utf8(&line[..end])
+                .ok()
+                .and_then(|line| line.split(';').next())
+                .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+                .ok_or_else(|| {
+                    S3Error::new(
+                        StatusCode::BAD_REQUEST,
+                        "InvalidRequest",
+                        "Invalid aws-chunked encoding.",
+                    )
+                })?;
+            self.remaining = size;
+            self.done = size == 0;
+        }
+        Ok(out)
+    }
+}
+
+/// 由大小和修改时间生成, alist 不返回文件的 md5
+fn etag(meta: &Metadata) -> String {
+    let modified = meta
+        .modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    format!("\"{:x}-{:x}\"", meta.size, modified)
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+fn xml_response(status: StatusCode, xml: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/xml")
+        .body(Body::from(xml))
+        .unwrap()
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
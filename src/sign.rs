@@ -0,0 +1,76 @@
+use super::Error;
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 本地生成/校验 /d/ 直链签名, 与服务端 HMAC 签名算法一致
+///
+/// token 为站点令牌, 可通过 admin::get_setting(server, token, "token") 获取
+pub struct Signer {
+    server: String,
+    token: String,
+}
+
+impl Signer {
+    pub fn new(server: &str, token: &str) -> Self {
+        Signer {
+            server: server.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    /// 签名, expire 为过期时间戳(秒), 0 表示永不过期
+    pub fn sign(&self, path: &str, expire: u64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.token.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(format!("{}:{}", path, expire).as_bytes());
+        format!(
+            "{}:{}",
+            URL_SAFE.encode(mac.finalize().into_bytes()),
+            expire
+        )
+    }
+
+    /// 生成直链 /d/<path>?sign=..., expires 为 None 时永不过期
+    pub fn sign_url(&self, path: &str, expires: Option<Duration>) -> Result<String, Error> {
+        let expire = match expires {
+            Some(expires) => (SystemTime::now() + expires)
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| Error::Other(e.to_string()))?
+                .as_secs(),
+            None => 0,
+        };
+        let mut url = url::Url::parse(&self.server).map_err(|e| Error::Other(e.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| Error::Other(format!("invalid server url: {}", self.server)))?
+            .pop_if_empty()
+            .push("d")
+            .extend(path.split('/').filter(|s| !s.is_empty()));
+        url.query_pairs_mut()
+            .append_pair("sign", &self.sign(path, expire));
+        Ok(url.to_string())
+    }
+
+    /// 校验签名是否有效
+    pub fn verify(&self, path: &str, sign: &str) -> Result<(), Error> {
+        let expire = match sign.rsplit_once(':') {
+            Some((_, expire)) if !expire.is_empty() => expire,
+            _ => return Err(Error::Other("expire missing".to_string())),
+        };
+        let expire: u64 = expire
+            .parse()
+            .map_err(|_| Error::Other("expire invalid".to_string()))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::Other(e.to_string()))?
+            .as_secs();
+        if expire != 0 && expire < now {
+            return Err(Error::Other("sign expired".to_string()));
+        }
+        if self.sign(path, expire) != sign {
+            return Err(Error::Other("sign invalid".to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! RFC 3339 时间的格式化和解析, alist、S3 网关和模拟服务端共用
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 格式化为 UTC 时间, frac_digits 为秒的小数位数 (0 到 9), 如 2024-01-02T03:04:05.000Z
+#[cfg(any(test, feature = "mock", feature = "s3"))]
+pub(crate) fn format_rfc3339(time: SystemTime, frac_digits: u32) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let rem = secs.rem_euclid(86400);
+    let mut result = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    );
+    if frac_digits > 0 {
+        let digits = frac_digits.min(9);
+        let frac = elapsed.subsec_nanos() / 10u32.pow(9 - digits);
+        result.push_str(&format!(".{:0width$}", frac, width = digits as usize));
+    }
+    result.push('Z');
+    result
+}
+
+/// 解析 alist 返回的修改时间, 如 2024-01-02T03:04:05.123+08:00
+pub(crate) fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    let num = |range: std::ops::Range<usize>| value.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    let rest = value.get(19..)?;
+    // 小数部分
+    let frac_len = rest
+        .strip_prefix('.')
+        .map_or(0, |r| r.bytes().take_while(u8::is_ascii_digit).count());
+    let nanos = match frac_len {
+        0 => 0,
+        n => {
+            let digits = &rest[1..1 + n.min(9)];
+            digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32)
+        }
+    };
+    let zone = &rest[if frac_len > 0 { frac_len + 1 } else { 0 }..];
+    let offset = match zone {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match zone.get(0..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let hours = zone.get(1..3)?.parse::<i64>().ok()?;
+            let minutes = zone.get(4..6)?.parse::<i64>().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+// 公历日期与 1970-01-01 起的天数互相转换
+// http://howardhinnant.github.io/date_algorithms.html
+#[cfg(any(test, feature = "mock", feature = "s3"))]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
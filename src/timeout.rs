@@ -0,0 +1,61 @@
+use super::Error;
+use std::future::Future;
+use std::time::Duration;
+
+/// 请求超时设置, 为 None 时不限制
+///
+/// 可以对单次调用使用不同的 read 和 total, 与原客户端共用连接池:
+///
+/// ```
+/// use alistapi::{timeout::Timeouts, AlistClient};
+/// use std::time::Duration;
+///
+/// let client = AlistClient::new("http://127.0.0.1:5244");
+/// let slow = client.clone().with_timeouts(Timeouts {
+///     total: Some(Duration::from_secs(120)),
+///     ..client.timeouts()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    // 建立连接的超时, 由连接池使用, 见 AlistClient::with_timeouts
+    pub connect: Option<Duration>,
+    // 等待响应以及两次读取响应数据之间的最长间隔
+    pub read: Option<Duration>,
+    // 单次接口请求的总时间, 不适用于上传和下载文件
+    pub total: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Some(Duration::from_secs(30)),
+            read: Some(Duration::from_secs(60)),
+            total: None,
+        }
+    }
+}
+
+impl Timeouts {
+    /// 不设置任何超时
+    pub fn none() -> Self {
+        Timeouts {
+            connect: None,
+            read: None,
+            total: None,
+        }
+    }
+}
+
+/// 超时后返回 Error::Timeout
+pub(crate) async fn with_timeout<T, F>(duration: Option<Duration>, fut: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    match duration {
+        Some(duration) => tokio::time::timeout(duration, fut)
+            .await
+            .unwrap_or(Err(Error::Timeout)),
+        None => fut.await,
+    }
+}
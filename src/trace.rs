@@ -0,0 +1,168 @@
+//! 请求的 tracing span, 需要启用 tracing feature, 未启用时为空实现
+//!
+//! span 名称为 `alist.request`, 字段包括 request_id、method、endpoint、path、
+//! status、code、attempts、bytes_sent、bytes_received 和 duration_ms.
+//! Authorization 和请求体中的密码不会被记录.
+use super::client::ApiRequest;
+use super::Error;
+
+#[cfg(feature = "tracing")]
+pub(crate) use enabled::RequestSpan;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use disabled::RequestSpan;
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use super::{ApiRequest, Error};
+    use crate::cassette::redact;
+    use crate::Response;
+    use serde::de::IgnoredAny;
+    use std::future::Future;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Instant;
+    use tracing::field::Empty;
+    use tracing::{Instrument, Span};
+
+    static REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+    pub(crate) struct RequestSpan {
+        span: Span,
+        start: Instant,
+    }
+
+    impl RequestSpan {
+        pub fn request(req: &ApiRequest) -> Self {
+            let span = RequestSpan::new(req.method.as_str(), &req.endpoint, remote_path(req));
+            if let Some(json) = &req.json {
+                let mut json = json.clone();
+                redact(&mut json);
+                tracing::trace!(parent: &span.span, request = %json, "request body");
+            }
+            span
+        }
+
+        /// 下载文件, url 中的签名不会被记录
+        pub fn download(url: &str) -> Self {
+            let endpoint = url.split('?').next().unwrap_or_default();
+            RequestSpan::new("GET", endpoint, None)
+        }
+
+        fn new(method: &str, endpoint: &str, path: Option<&str>) -> Self {
+            let span = tracing::debug_span!(
+                "alist.request",
+                request_id = REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+                method,
+                endpoint,
+                path,
+                status = Empty,
+                code = Empty,
+                attempts = Empty,
+                bytes_sent = Empty,
+                bytes_received = Empty,
+                duration_ms = Empty,
+            );
+            RequestSpan {
+                span,
+                start: Instant::now(),
+            }
+        }
+
+        pub async fn instrument<F: Future>(&self, fut: F) -> F::Output {
+            fut.instrument(self.span.clone()).await
+        }
+
+        pub fn attempt(&self, attempt: u32) {
+            self.span.record("attempts", attempt);
+        }
+
+        pub fn retry(&self, attempt: u32, error: Option<&Error>) {
+            match error {
+                Some(error) => tracing::debug!(parent: &self.span, attempt, %error, "retrying"),
+                None => tracing::debug!(parent: &self.span, attempt, "retrying"),
+            }
+        }
+
+        pub fn sent(&self, bytes: u64) {
+            self.span.record("bytes_sent", bytes);
+        }
+
+        pub fn received(&self, bytes: u64) {
+            self.span.record("bytes_received", bytes);
+        }
+
+        pub fn status(&self, status: u16) {
+            self.span.record("status", status);
+        }
+
+        /// 记录 HTTP 状态码和接口 code
+        pub fn response(&self, status: u16, body: &[u8]) {
+            self.status(status);
+            self.received(body.len() as u64);
+            if let Ok(resp) = serde_json::from_slice::<Response<IgnoredAny>>(body) {
+                self.span.record("code", resp.code);
+            }
+        }
+
+        pub fn finish<T>(&self, result: &Result<T, Error>) {
+            let duration_ms = self.start.elapsed().as_millis() as u64;
+            self.span.record("duration_ms", duration_ms);
+            match result {
+                Ok(_) => tracing::debug!(parent: &self.span, duration_ms, "request finished"),
+                Err(error) => {
+                    if let Some(code) = error.code() {
+                        self.span.record("code", code);
+                    }
+                    tracing::debug!(parent: &self.span, duration_ms, %error, "request failed")
+                }
+            }
+        }
+    }
+
+    /// 请求操作的远程路径, 只取路径字段, 不包括密码
+    fn remote_path(req: &ApiRequest) -> Option<&str> {
+        if let Some((_, path)) = req.headers.iter().find(|(k, _)| k == "File-Path") {
+            return Some(path);
+        }
+        let json = req.json.as_ref()?;
+        ["path", "src_dir", "dir", "parent"]
+            .iter()
+            .find_map(|key| json.get(key).and_then(|v| v.as_str()))
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod disabled {
+    use super::{ApiRequest, Error};
+    use std::future::Future;
+
+    pub(crate) struct RequestSpan;
+
+    impl RequestSpan {
+        pub fn request(_req: &ApiRequest) -> Self {
+            RequestSpan
+        }
+
+        pub fn download(_url: &str) -> Self {
+            RequestSpan
+        }
+
+        pub async fn instrument<F: Future>(&self, fut: F) -> F::Output {
+            fut.await
+        }
+
+        pub fn attempt(&self, _attempt: u32) {}
+
+        pub fn retry(&self, _attempt: u32, _error: Option<&Error>) {}
+
+        pub fn sent(&self, _bytes: u64) {}
+
+        pub fn received(&self, _bytes: u64) {}
+
+        pub fn status(&self, _status: u16) {}
+
+        pub fn response(&self, _status: u16, _body: &[u8]) {}
+
+        pub fn finish<T>(&self, _result: &Result<T, Error>) {}
+    }
+}
@@ -0,0 +1,479 @@
+//! 与存储无关的文件系统接口, 可以在本地目录和 alist 服务端之间切换
+//!
+//! ```no_run
+//! use alistapi::vfs::{LocalFs, RemoteFs};
+//! use alistapi::{AlistClient, Error};
+//!
+//! async fn count_files(fs: &dyn RemoteFs, dir: &str) -> Result<usize, Error> {
+//!     Ok(fs.read_dir(dir).await?.iter().filter(|m| !m.is_dir).count())
+//! }
+//!
+//! # async fn run() -> Result<(), Error> {
+//! let local = LocalFs::new("/tmp/backup");
+//! let remote = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! assert_eq!(count_files(&local, "/").await?, count_files(&remote, "/").await?);
+//! # Ok(())
+//! # }
+//! ```
+use super::client::AlistClient;
+use super::fs::{CopyParams, DeleteParams, FileParams, MoveParams, UploadParams};
+use super::middleware::BoxFuture;
+use super::time::parse_rfc3339;
+use super::trace::RequestSpan;
+use super::Error;
+use futures_util::{stream, TryStreamExt};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::io::StreamReader;
+
+pub type Reader = Pin<Box<dyn AsyncRead + Send>>;
+pub type Writer = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// 文件或目录的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    // 无法获取时为 None
+    pub modified: Option<SystemTime>,
+}
+
+/// 文件系统操作, 路径均为以 / 开头的绝对路径
+pub trait RemoteFs: Send + Sync {
+    /// 列出目录下的文件和子目录
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Vec<Metadata>, Error>>;
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Metadata, Error>>;
+
+    /// 创建目录, 包括不存在的上级目录
+    fn create_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// 重命名或移动到其它目录, to 为新的完整路径
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// 复制文件或目录到 to_dir 下, 保留原名称
+    fn copy<'a>(&'a self, from: &'a str, to_dir: &'a str) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// 删除文件或目录, 目录会连同内容一起删除
+    fn remove<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>>;
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Reader, Error>>;
+
+    /// 创建或覆盖文件, 写入完成后需要调用 shutdown
+    fn create_write<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Writer, Error>>;
+}
+
+/// 拆分为 (父目录, 名称), 忽略末尾的 /
+///
+/// ```
+/// use alistapi::vfs::split_path;
+///
+/// assert_eq!(split_path("/cloud/a.txt/"), ("/cloud".to_string(), "a.txt".to_string()));
+/// assert_eq!(split_path("/a.txt"), ("/".to_string(), "a.txt".to_string()));
+/// ```
+pub fn split_path(path: &str) -> (String, String) {
+    let path = path.trim_end_matches('/');
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/".to_string(), name.to_string()),
+        Some((dir, name)) => (dir.to_string(), name.to_string()),
+        None => ("/".to_string(), path.to_string()),
+    }
+}
+
+pub(crate) fn join_path(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// 复制为完整路径 to, 服务端复制只能保留原名称, 不能借助重命名时通过读写复制
+#[cfg(any(feature = "webdav", feature = "s3"))]
+pub(crate) async fn copy_as(fs: &dyn RemoteFs, from: &str, to: &str) -> Result<(), Error> {
+    use tokio::io::AsyncWriteExt;
+
+    let (src_dir, src_name) = split_path(from);
+    let (dst_dir, dst_name) = split_path(to);
+    if src_name == dst_name {
+        return fs.copy(from, &dst_dir).await;
+    }
+    // 目标目录下已有同名文件时, 复制会覆盖它
+    let staged = join_path(&dst_dir, &src_name);
+    if src_dir != dst_dir && fs.metadata(&staged).await.is_err() {
+        fs.copy(from, &dst_dir).await?;
+        return fs.rename(&staged, to).await;
+    }
+    let mut reader = fs.open_read(from).await?;
+    let mut writer = fs.create_write(to).await?;
+    tokio::io::copy(&mut reader, &mut writer).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+fn file_params(path: &str) -> FileParams {
+    FileParams {
+        path: Some(path.to_string()),
+        ..Default::default()
+    }
+}
+
+impl AlistClient {
+    async fn move_between(&self, src_dir: &str, dst_dir: &str, name: &str) -> Result<(), Error> {
+        self.move_file(MoveParams {
+            src_dir: src_dir.to_string(),
+            dst_dir: dst_dir.to_string(),
+            names: vec![name.to_string()],
+        })
+        .await
+    }
+}
+
+impl RemoteFs for AlistClient {
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Vec<Metadata>, Error>> {
+        Box::pin(async move {
+            let data = self.listdir(file_params(path)).await?;
+            Ok(data
+                .content
+                .into_iter()
+                .map(|f| Metadata {
+                    modified: parse_rfc3339(&f.modified),
+                    size: u64::try_from(f.size).unwrap_or(u64::MAX),
+                    is_dir: f.is_dir,
+                    name: f.name,
+                })
+                .collect())
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Metadata, Error>> {
+        Box::pin(async move {
+            let info = self.fileinfo(file_params(path)).await?;
+            Ok(Metadata {
+                modified: parse_rfc3339(&info.modified),
+                size: u64::try_from(info.size).unwrap_or(u64::MAX),
+                is_dir: info.is_dir,
+                name: info.name,
+            })
+        })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(self.mkdir(path))
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (src_dir, src_name) = split_path(from);
+            let (dst_dir, dst_name) = split_path(to);
+            if src_dir == dst_dir {
+                return AlistClient::rename(self, from, &dst_name).await;
+            }
+            if src_name == dst_name {
+                return self.move_between(&src_dir, &dst_dir, &src_name).await;
+            }
+            // 服务端只能在同一目录下重命名, 移动时保留名称. 先改为不会冲突的临时名称,
+            // 避免与源目录中的 dst_name 或目标目录中的 src_name 冲突, 失败时恢复原名称
+            let temp = temp_name(&src_name);
+            AlistClient::rename(self, from, &temp).await?;
+            let temp_path = join_path(&src_dir, &temp);
+            if let Err(err) = self.move_between(&src_dir, &dst_dir, &temp).await {
+                let _ = AlistClient::rename(self, &temp_path, &src_name).await;
+                return Err(err);
+            }
+            let moved = join_path(&dst_dir, &temp);
+            if let Err(err) = AlistClient::rename(self, &moved, &dst_name).await {
+                if self.move_between(&dst_dir, &src_dir, &temp).await.is_ok() {
+                    let _ = AlistClient::rename(self, &temp_path, &src_name).await;
+                }
+                return Err(err);
+            }
+            Ok(())
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a str, to_dir: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (src_dir, name) = split_path(from);
+            self.copy_file(CopyParams {
+                src_dir,
+                dst_dir: to_dir.to_string(),
+                names: vec![name],
+            })
+            .await
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let (dir, name) = split_path(path);
+            self.remove_directory(DeleteParams {
+                dir,
+                names: vec![name],
+            })
+            .await
+        })
+    }
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Reader, Error>> {
+        Box::pin(async move {
+            let info = self.fileinfo(file_params(path)).await?;
+            if info.is_dir {
+                return Err(Error::Other(format!("{} is a directory", info.name)));
+            }
+            // raw_url 可能指向第三方存储, 不能携带 token
+            let span = RequestSpan::download(&info.row_url);
+            let resp = self.get_raw(&info.row_url, false, None, &span).await?;
+            // 与 download_file 一样限制每次读取的时间, 取消时停止读取
+            let stream =
+                stream::try_unfold((self.clone(), resp), |(client, mut resp)| async move {
+                    let chunk = client.cancellable(client.read_chunk(&mut resp)).await?;
+                    Ok::<_, Error>(chunk.map(|chunk| (chunk, (client, resp))))
+                });
+            Ok(Box::pin(StreamReader::new(stream.map_err(io::Error::from))) as Reader)
+        })
+    }
+
+    fn create_write<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Writer, Error>> {
+        Box::pin(async move {
+            let temp = temp_path();
+            let file = File::create(&temp).await?;
+            Ok(Box::pin(AlistWriter {
+                file: Some(file),
+                temp,
+                client: self.clone(),
+                remote: path.to_string(),
+                upload: None,
+            }) as Writer)
+        })
+    }
+}
+
+/// 跨目录重命名时使用的临时名称
+fn temp_name(name: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        ".{}.{}-{}.renaming",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn temp_path() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "alistapi-upload-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// 先写入本地临时文件, shutdown 时上传, 上传需要提前知道文件大小
+struct AlistWriter {
+    file: Option<File>,
+    temp: PathBuf,
+    client: AlistClient,
+    remote: String,
+    upload: Option<BoxFuture<'static, Result<(), Error>>>,
+}
+
+impl AlistWriter {
+    fn file(&mut self) -> io::Result<Pin<&mut File>> {
+        match &mut self.file {
+            Some(file) => Ok(Pin::new(file)),
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "writer is closed",
+            )),
+        }
+    }
+}
+
+impl AsyncWrite for AlistWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().file()?.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().file {
+            Some(file) => Pin::new(file).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(file) = &mut this.file {
+            ready!(Pin::new(file).poll_shutdown(cx))?;
+            this.file = None;
+            let client = this.client.clone();
+            let (remote_path, remote_name) = split_path(&this.remote);
+            let local_file = this.temp.to_string_lossy().into_owned();
+            this.upload = Some(Box::pin(async move {
+                client
+                    .upload(UploadParams {
+                        local_file,
+                        remote_path,
+                        remote_name,
+                    })
+                    .await
+            }));
+        }
+        let Some(upload) = &mut this.upload else {
+            return Poll::Ready(Ok(()));
+        };
+        let result = ready!(upload.as_mut().poll(cx));
+        this.upload = None;
+        let _ = std::fs::remove_file(&this.temp);
+        Poll::Ready(result.map_err(io::Error::from))
+    }
+}
+
+impl Drop for AlistWriter {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.temp);
+    }
+}
+
+/// 本地目录, 路径相对于 root, 不能访问 root 以外的文件
+#[derive(Debug, Clone)]
+pub struct LocalFs {
+    root: PathBuf,
+}
+
+impl LocalFs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalFs { root: root.into() }
+    }
+
+    /// 逐级解析符号链接, 链接指向 root 以外时返回错误
+    async fn resolve(&self, path: &str) -> Result<PathBuf, Error> {
+        let invalid = || Error::Other(format!("invalid path {}", path));
+        let root = tokio::fs::canonicalize(&self.root).await?;
+        let mut resolved = root.clone();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::RootDir | Component::CurDir => continue,
+                _ => return Err(invalid()),
+            }
+            match tokio::fs::canonicalize(&resolved).await {
+                Ok(real) => resolved = real,
+                // 不存在的路径可以创建, 但不能是指向别处的悬空链接
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    if tokio::fs::symlink_metadata(&resolved).await.is_ok() {
+                        return Err(invalid());
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+            if !resolved.starts_with(&root) {
+                return Err(invalid());
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+async fn local_metadata(path: &Path) -> Result<Metadata, Error> {
+    let meta = tokio::fs::metadata(path).await?;
+    Ok(Metadata {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        is_dir: meta.is_dir(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        modified: meta.modified().ok(),
+    })
+}
+
+/// 递归复制文件或目录
+async fn copy_local(from: PathBuf, to: PathBuf) -> Result<(), Error> {
+    let mut pending = vec![(from, to)];
+    while let Some((from, to)) = pending.pop() {
+        if !tokio::fs::metadata(&from).await?.is_dir() {
+            tokio::fs::copy(&from, &to).await?;
+            continue;
+        }
+        tokio::fs::create_dir_all(&to).await?;
+        let mut entries = tokio::fs::read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            pending.push((entry.path(), to.join(entry.file_name())));
+        }
+    }
+    Ok(())
+}
+
+impl RemoteFs for LocalFs {
+    fn read_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Vec<Metadata>, Error>> {
+        Box::pin(async move {
+            let mut entries = tokio::fs::read_dir(self.resolve(path).await?).await?;
+            let mut result = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                result.push(local_metadata(&entry.path()).await?);
+            }
+            result.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(result)
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Metadata, Error>> {
+        Box::pin(async move { local_metadata(&self.resolve(path).await?).await })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { Ok(tokio::fs::create_dir_all(self.resolve(path).await?).await?) })
+    }
+
+    fn rename<'a>(&'a self, from: &'a str, to: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            Ok(tokio::fs::rename(self.resolve(from).await?, self.resolve(to).await?).await?)
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a str, to_dir: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let from = self.resolve(from).await?;
+            let name = from
+                .file_name()
+                .ok_or_else(|| Error::Other("cannot copy the root directory".to_string()))?;
+            let to = self.resolve(to_dir).await?.join(name);
+            copy_local(from, to).await
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let path = self.resolve(path).await?;
+            if tokio::fs::metadata(&path).await?.is_dir() {
+                tokio::fs::remove_dir_all(path).await?;
+            } else {
+                tokio::fs::remove_file(path).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Reader, Error>> {
+        Box::pin(async move {
+            let file = File::open(self.resolve(path).await?).await?;
+            Ok(Box::pin(file) as Reader)
+        })
+    }
+
+    fn create_write<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<Writer, Error>> {
+        Box::pin(async move {
+            let file = File::create(self.resolve(path).await?).await?;
+            Ok(Box::pin(file) as Writer)
+        })
+    }
+}
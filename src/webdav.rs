@@ -0,0 +1,246 @@
+//! 本地 WebDAV 网关, 需要启用 webdav feature
+//!
+//! 将 WebDAV 请求转换为 [`RemoteFs`] 操作, 用于 alist 服务端时接口请求经过客户端的
+//! 重试、中间件和 token 设置, GET 读取文件内容时只调用中间件的 prepare_raw, 不会重试.
+//! 只支持 DAV class 1, 不支持 LOCK 和 PROPPATCH.
+//!
+//! ```no_run
+//! use alistapi::webdav::WebDavServer;
+//! use alistapi::AlistClient;
+//! use std::sync::Arc;
+//!
+//! # async fn run() -> Result<(), alistapi::Error> {
+//! let client = AlistClient::new("http://127.0.0.1:5244").with_token("token");
+//! let server = WebDavServer::start(Arc::new(client), ([127, 0, 0, 1], 8080).into()).await?;
+//! println!("WebDAV: {}", server.url());
+//! # Ok(())
+//! # }
+//! ```
+use super::gateway::{escape, is_not_found, HttpServer};
+use super::vfs::{copy_as, join_path, Metadata, RemoteFs};
+use super::Error;
+use hyper::body::HttpBody;
+use hyper::header::HeaderValue;
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+// href 中需要编码的字符, 保留 RFC 3986 的非保留字符
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 在后台运行的 WebDAV 服务, drop 时停止
+pub struct WebDavServer {
+    server: HttpServer,
+}
+
+impl WebDavServer {
+    /// 监听 addr, 端口为 0 时随机选择
+    pub async fn start(fs: Arc<dyn RemoteFs>, addr: SocketAddr) -> Result<WebDavServer, Error> {
+        let server = HttpServer::start(addr, move |req| handle(fs.clone(), req))?;
+        Ok(WebDavServer { server })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+}
+
+async fn handle(fs: Arc<dyn RemoteFs>, req: Request<Body>) -> Response<Body> {
+    dispatch(&*fs, req)
+        .await
+        .unwrap_or_else(|err| error_response(&err))
+}
+
+async fn dispatch(fs: &dyn RemoteFs, req: Request<Body>) -> Result<Response<Body>, Error> {
+    let path = decode_path(req.uri().path());
+    match req.method().as_str() {
+        "OPTIONS" => Ok(Response::builder()
+            .header("DAV", "1")
+            .header(
+                "Allow",
+                "OPTIONS, PROPFIND, GET, HEAD, PUT, MKCOL, MOVE, COPY, DELETE",
+            )
+            .body(Body::empty())
+            .unwrap()),
+        "PROPFIND" => {
+            let depth = header(&req, "Depth").unwrap_or("1");
+            propfind(fs, &path, depth != "0").await
+        }
+        "GET" | "HEAD" => {
+            let meta = fs.metadata(&path).await?;
+            if meta.is_dir {
+                return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            let mut resp = Response::builder().header("Content-Length", meta.size);
+            if let Some(modified) = meta.modified {
+                resp = resp.header("Last-Modified", httpdate::fmt_http_date(modified));
+            }
+            let body = if req.method() == "HEAD" {
+                Body::empty()
+            } else {
+                Body::wrap_stream(ReaderStream::new(fs.open_read(&path).await?))
+            };
+            Ok(resp.body(body).unwrap())
+        }
+        "PUT" => {
+            let mut writer = fs.create_write(&path).await?;
+            let mut body = req.into_body();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.map_err(|e| Error::Other(e.to_string()))?;
+                writer.write_all(&chunk).await?;
+            }
+            writer.shutdown().await?;
+            Ok(status(StatusCode::CREATED))
+        }
+        "MKCOL" => {
+            if fs.metadata(&path).await.is_ok() {
+                return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+            }
+            fs.create_dir(&path).await?;
+            Ok(status(StatusCode::CREATED))
+        }
+        "DELETE" => {
+            // 服务端删除不存在的文件时不会报错
+            fs.metadata(&path).await?;
+            fs.remove(&path).await?;
+            Ok(status(StatusCode::NO_CONTENT))
+        }
+        "MOVE" | "COPY" => {
+            let Some(dest) = header(&req, "Destination").and_then(destination_path) else {
+                return Ok(status(StatusCode::BAD_REQUEST));
+            };
+            let overwrite = header(&req, "Overwrite") != Some("F");
+            let exists = fs.metadata(&dest).await.is_ok();
+            if exists {
+                if !overwrite {
+                    return Ok(status(StatusCode::PRECONDITION_FAILED));
+                }
+                fs.remove(&dest).await?;
+            }
+            if req.method() == "MOVE" {
+                fs.rename(&path, &dest).await?;
+            } else {
+                copy_as(fs, &path, &dest).await?;
+            }
+            Ok(status(if exists {
+                StatusCode::NO_CONTENT
+            } else {
+                StatusCode::CREATED
+            }))
+        }
+        _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+    }
+}
+
+async fn propfind(fs: &dyn RemoteFs, path: &str, children: bool) -> Result<Response<Body>, Error> {
+    let meta = fs.metadata(path).await?;
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    write_response(&mut xml, path, &meta);
+    if meta.is_dir && children {
+        for child in fs.read_dir(path).await? {
+            let child_path = join_path(path, &child.name);
+            write_response(&mut xml, &child_path, &child);
+        }
+    }
+    xml.push_str("</D:multistatus>\n");
+    Ok(Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(Body::from(xml))
+        .unwrap())
+}
+
+fn write_response(xml: &mut String, path: &str, meta: &Metadata) {
+    let mut href: String = path
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    if meta.is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    let _ = write!(
+        xml,
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop><D:displayname>{}</D:displayname>",
+        escape(&href),
+        escape(&meta.name)
+    );
+    if meta.is_dir {
+        xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        let _ = write!(
+            xml,
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+            meta.size
+        );
+    }
+    if let Some(modified) = meta.modified {
+        let _ = write!(
+            xml,
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            httpdate::fmt_http_date(modified)
+        );
+    }
+    xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+fn header<'a>(req: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+fn decode_path(path: &str) -> String {
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    match path.trim_end_matches('/') {
+        "" => "/".to_string(),
+        path => path.to_string(),
+    }
+}
+
+/// Destination 为完整的 url, 只取路径部分
+fn destination_path(dest: &str) -> Option<String> {
+    let path = match dest.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => dest,
+    };
+    Some(decode_path(path))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = code;
+    resp
+}
+
+/// 将错误转换为 HTTP 状态码
+fn error_response(err: &Error) -> Response<Body> {
+    let code = match err {
+        err if is_not_found(err) => StatusCode::NOT_FOUND,
+        Error::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        Error::Api {
+            code: 401 | 403, ..
+        } => StatusCode::FORBIDDEN,
+        Error::Other(_) | Error::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    let mut resp = Response::new(Body::from(err.to_string()));
+    *resp.status_mut() = code;
+    resp.headers_mut().insert(
+        "Content-Type",
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    resp
+}